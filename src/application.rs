@@ -3,15 +3,16 @@ use std::{
     io::Write,
     sync::{mpsc, Arc},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
     backend::Backend,
+    config::Config,
     mode::*,
     platform::{Key, Platform, PlatformEventReader},
     tool::*,
-    ui::Drawer,
+    ui::{Color, Drawer},
 };
 
 enum Event {
@@ -20,6 +21,7 @@ enum Event {
     Response(ModeResponse),
     ModeChange(ModeKind, ModeChangeInfo),
     ModeRevert,
+    Notice(String),
 }
 
 #[derive(Clone)]
@@ -36,12 +38,39 @@ impl EventSender {
     pub fn send_mode_revert(&self) {
         self.0.send(Event::ModeRevert).unwrap();
     }
+
+    // shown in place of the mode name in the header until the next key
+    pub fn send_notice(&self, notice: String) {
+        self.0.send(Event::Notice(notice)).unwrap();
+    }
+
+    #[cfg(test)]
+    pub fn channel() -> (Self, EventReceiver) {
+        let (sender, receiver) = mpsc::channel();
+        (Self(sender), EventReceiver(receiver))
+    }
+}
+
+#[cfg(test)]
+pub struct EventReceiver(mpsc::Receiver<Event>);
+#[cfg(test)]
+impl EventReceiver {
+    // waits for the next mode response, skipping any other event
+    pub fn recv_response(&self) -> Option<ModeResponse> {
+        loop {
+            match self.0.recv_timeout(Duration::from_secs(5)).ok()? {
+                Event::Response(response) => return Some(response),
+                _ => continue,
+            }
+        }
+    }
 }
 
 #[derive(Default)]
 struct Application {
     mode: ModeBuf,
     spinner_state: u8,
+    notice: Option<String>, // replaces the mode name in the header until the next key
 }
 impl Application {
     pub fn current_mode(&mut self) -> &mut dyn ModeTrait {
@@ -49,6 +78,7 @@ impl Application {
     }
 
     pub fn on_key(&mut self, ctx: &ModeContext, key: Key) -> bool {
+        self.notice = None;
         let status = self.current_mode().on_key(ctx, key);
 
         if !status.pending_input {
@@ -67,12 +97,43 @@ impl Application {
                 Key::Char('b') => Some(ModeKind::Branches),
                 Key::Char('t') => Some(ModeKind::Tags),
                 Key::Char('S') => Some(ModeKind::Stash),
+                Key::Char('H') => Some(ModeKind::History),
                 _ => None,
             };
 
             if let Some(target_mode_kind) = target_mode_kind {
                 self.mode.enter_mode(ctx, target_mode_kind, ModeChangeInfo::new(self.mode.mode_kind()));
             }
+
+            if key == Key::Char('Y') {
+                let result = ctx.backend.current_branch().and_then(|branch| {
+                    Platform::set_clipboard(&branch)?;
+                    Ok(format!("copied '{}'", branch))
+                });
+                self.notice = Some(match result {
+                    Ok(notice) => notice,
+                    Err(error) => error.lines().last().unwrap_or("").into(),
+                });
+            }
+
+            if key == Key::Char('!') {
+                // the current dir is already the repository root
+                if let Err(error) = Platform::suspend(Platform::run_shell) {
+                    log(format!("could not run shell: {}\n", error));
+                }
+
+                // anything could have changed so refresh, falling back to status for modes that need extra info
+                let mode_kind = match self.mode.mode_kind() {
+                    kind @ (ModeKind::Status
+                    | ModeKind::Log
+                    | ModeKind::Branches
+                    | ModeKind::Tags
+                    | ModeKind::Stash
+                    | ModeKind::History) => kind,
+                    _ => ModeKind::Status,
+                };
+                self.mode.enter_mode(ctx, mode_kind, ModeChangeInfo::new(self.mode.mode_kind()));
+            }
         }
 
         true
@@ -87,6 +148,11 @@ impl Application {
         }
     }
 
+    // requests the current list again in place, keeping the cursor, the selection and any output
+    pub fn refresh(&mut self, ctx: &ModeContext) {
+        self.current_mode().refresh(ctx);
+    }
+
     pub fn is_waiting_response(&mut self) -> bool {
         self.current_mode().is_waiting_response()
     }
@@ -99,14 +165,20 @@ impl Application {
             false => b' ',
         };
 
-        let (mode_name, left_help, right_help) = self.current_mode().header();
-        drawer.header(mode_name, left_help, right_help, spinner);
+        let notice = self.notice.as_deref();
+        let (mode_name, left_help, right_help) = self.mode.mode().header();
+        drawer.header(notice.unwrap_or(mode_name), left_help, right_help, spinner);
     }
 
     pub fn draw_body(&mut self, drawer: &mut Drawer) {
         //log(format!("draw body, mode:\n, {:?}\n", self.mode));
 
+        let buf_len = drawer.buf_len();
         self.current_mode().draw(drawer);
+        // a mode fetching its first data draws nothing until the response arrives
+        if drawer.buf_len() == buf_len && self.is_waiting_response() {
+            drawer.fmt(format_args!("{}loading...{}", Color::DarkGray, Color::White));
+        }
         drawer.clear_to_bottom();
     }
 }
@@ -134,33 +206,75 @@ fn terminal_event_loop(mut event_reader: PlatformEventReader, sender: mpsc::Send
     }
 }
 
-pub fn run(platform_event_reader: PlatformEventReader, backend: Arc<dyn Backend>) {
+pub fn run(platform_event_reader: PlatformEventReader, backend: Arc<dyn Backend>, config: Config, mode_kind: ModeKind) {
     let (event_sender, event_receiver) = mpsc::channel();
 
-    let mut ctx =
-        ModeContext { backend, event_sender: EventSender(event_sender.clone()), viewport_size: Platform::terminal_size() };
+    let mut ctx = ModeContext {
+        backend,
+        config: Arc::new(config),
+        event_sender: EventSender(event_sender.clone()),
+        viewport_size: Platform::terminal_size(),
+        last_output: Arc::default(),
+        toggles: Arc::default(),
+    };
 
     let _ = thread::spawn(move || {
         terminal_event_loop(platform_event_reader, event_sender);
     });
 
     let mut application = Application::default();
-    application.mode.enter_mode(&ctx, ModeKind::default(), ModeChangeInfo::new(ModeKind::default()));
+    application.mode.enter_mode(&ctx, mode_kind.clone(), ModeChangeInfo::new(mode_kind));
+
+    if ctx.config.auto_fetch {
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            // failing to fetch (e.g. when offline) is not worth interrupting anyone
+            if ctx.backend.fetch().is_ok() {
+                if let Ok(info) = ctx.backend.status() {
+                    ctx.event_sender.send_response(ModeResponse::Status(status::Response::Header(info.header)));
+                }
+            }
+        });
+    }
 
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
     let mut stdout_buf = Vec::new();
 
     const TIMEOUT: Duration = Duration::from_millis(100);
+    let mut last_refresh = Instant::now();
 
+    // the first frame shows the entered mode right away instead of waiting for its data
+    let mut draw_body = true;
     loop {
+        // timeouts only animate the spinner, every event (a mode change included) redraws the body
+        let mut drawer = Drawer::new(stdout_buf, ctx.viewport_size);
+        application.draw_header(&mut drawer);
+        if draw_body {
+            application.draw_body(&mut drawer);
+            drawer.status_line(&ctx.toggles.lock().unwrap());
+        }
+        stdout_buf = drawer.take_buf();
+
+        stdout.write_all(&stdout_buf).unwrap();
+        stdout.flush().unwrap();
+
+        if let Some(interval) = ctx.config.refresh_interval {
+            if last_refresh.elapsed() >= interval && !application.is_waiting_response() {
+                application.refresh(&ctx);
+                last_refresh = Instant::now();
+            }
+        }
+
         let event = if application.is_waiting_response() {
             event_receiver.recv_timeout(TIMEOUT)
+        } else if let Some(interval) = ctx.config.refresh_interval {
+            event_receiver.recv_timeout(interval.saturating_sub(last_refresh.elapsed()))
         } else {
             event_receiver.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected)
         };
 
-        let mut draw_body = true;
+        draw_body = true;
 
         match event {
             Ok(Event::Key(key)) => {
@@ -170,21 +284,14 @@ pub fn run(platform_event_reader: PlatformEventReader, backend: Arc<dyn Backend>
             }
             Ok(Event::Resize(width, height)) => {
                 ctx.viewport_size = (width, height);
+                application.current_mode().on_resize(ctx.viewport_size);
             }
             Ok(Event::Response(response)) => application.on_response(&ctx, response),
             Ok(Event::ModeChange(mode, info)) => application.mode.enter_mode(&ctx, mode, info),
             Ok(Event::ModeRevert) => application.mode.revert_mode(&ctx),
+            Ok(Event::Notice(notice)) => application.notice = Some(notice),
             Err(mpsc::RecvTimeoutError::Timeout) => draw_body = false,
             Err(mpsc::RecvTimeoutError::Disconnected) => break,
         }
-
-        let mut drawer = Drawer::new(stdout_buf, ctx.viewport_size);
-        application.draw_header(&mut drawer);
-        application.draw_body(&mut drawer);
-        if draw_body {}
-        stdout_buf = drawer.take_buf();
-
-        stdout.write_all(&stdout_buf).unwrap();
-        stdout.flush().unwrap();
     }
 }
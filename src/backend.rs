@@ -1,11 +1,21 @@
 use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
     path::PathBuf,
-    process::{Child, Command, Stdio},
-    sync::Arc,
+    process::{Child, Command, Output, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use crate::mode::{fuzzy_matches, FilterEntry};
 
+#[cfg(test)]
+pub mod fake;
 pub mod git;
 
 pub type BackendResult<T> = std::result::Result<T, String>;
@@ -52,14 +62,81 @@ impl FileStatus {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum MergeStrategy {
+    NoFastForward,
+    FastForward,
+    FastForwardOnly,
+}
+impl MergeStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "no-ff" => Some(Self::NoFastForward),
+            "ff" => Some(Self::FastForward),
+            "ff-only" => Some(Self::FastForwardOnly),
+            _ => None,
+        }
+    }
+}
+
+// an operation that stopped halfway, usually on conflicts, waiting to be continued or aborted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PendingOperation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+impl PendingOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Merge => "merge",
+            Self::Rebase => "rebase",
+            Self::CherryPick => "cherry-pick",
+            Self::Revert => "revert",
+            Self::Bisect => "bisect",
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct StatusInfo {
     pub header: String,
     pub entries: Vec<RevisionEntry>,
+    pub pending_operation: Option<PendingOperation>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DiffOptions {
+    pub ignore_whitespace: bool,
+    pub context_lines: usize,
+}
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { ignore_whitespace: false, context_lines: 3 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffStatEntry {
+    pub path: String,
+    pub insertions: usize, // binary files count no lines
+    pub deletions: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlameEntry {
+    pub hash: String, // abbreviated
+    pub author: String,
+    pub line: String,
 }
 
+#[derive(Default, Clone)]
 pub struct RevisionInfo {
     pub message: String,
     pub entries: Vec<RevisionEntry>,
+    pub parent_count: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -67,10 +144,12 @@ pub struct RevisionEntry {
     pub selected: bool,
     pub name: String,
     pub status: FileStatus,
+    pub conflict_count: usize, // conflict markers left in an unmerged file
+    pub rerere_resolved: bool, // an unmerged file resolved from a recorded resolution
 }
 impl RevisionEntry {
     pub fn new(name: String, status: FileStatus) -> Self {
-        Self { selected: false, name, status }
+        Self { selected: false, name, status, conflict_count: 0, rerere_resolved: false }
     }
 }
 impl FilterEntry for RevisionEntry {
@@ -79,29 +158,83 @@ impl FilterEntry for RevisionEntry {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Default, Debug, Clone)]
 pub struct LogEntry {
     pub graph: String,
     pub hash: String,
+    pub full_hash: String,
     pub date: String,
     pub author: String,
+    pub email: String,
     pub refs: String,
     pub message: String,
+    pub body: String,
+    pub source: String, // ref the commit was reached from, only when logging all refs
+}
+impl LogEntry {
+    // HEAD is decorated as 'HEAD -> branch' or, when detached, just 'HEAD'
+    pub fn is_head(&self) -> bool {
+        self.refs.split(", ").any(|r| r == "HEAD" || r.starts_with("HEAD -> "))
+    }
 }
 impl FilterEntry for LogEntry {
     fn fuzzy_matches(&self, pattern: &str) -> bool {
         fuzzy_matches(&self.message, pattern)
             || fuzzy_matches(&self.refs, pattern)
+            || fuzzy_matches(&self.source, pattern)
             || fuzzy_matches(&self.author, pattern)
+            || fuzzy_matches(&self.email, pattern)
             || fuzzy_matches(&self.date, pattern)
             || fuzzy_matches(&self.hash, pattern)
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RebaseAction {
+    Pick,
+    Reword,
+    Squash,
+    Fixup,
+    Drop,
+    // commands that are not editable here (exec, break, label, update-ref...), passed through to git as they are
+    Other(String),
+}
+impl RebaseAction {
+    pub const fn max_len() -> usize {
+        6
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Pick => "pick",
+            Self::Reword => "reword",
+            Self::Squash => "squash",
+            Self::Fixup => "fixup",
+            Self::Drop => "drop",
+            Self::Other(command) => command,
+        }
+    }
+
+    // reword and squash make git open an editor for the commit message
+    pub fn needs_editor(&self) -> bool {
+        matches!(self, Self::Reword | Self::Squash)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RebaseEntry {
+    pub action: RebaseAction,
+    pub hash: String,
+    pub message: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct BranchEntry {
     pub name: String,
     pub checked_out: bool,
+    pub remote: bool,
+    pub timestamp: i64, // of the last commit
+    pub upstream: Option<String>,
 }
 impl FilterEntry for BranchEntry {
     fn fuzzy_matches(&self, pattern: &str) -> bool {
@@ -124,6 +257,7 @@ pub struct StashEntry {
     pub id: usize,
     pub branch: String,
     pub message: String,
+    pub timestamp: i64,
 }
 impl FilterEntry for StashEntry {
     fn fuzzy_matches(&self, pattern: &str) -> bool {
@@ -133,73 +267,363 @@ impl FilterEntry for StashEntry {
 
 pub trait Backend: 'static + Send + Sync {
     fn status(&self) -> BackendResult<StatusInfo>;
-    fn commit(&self, message: &str, entries: &[RevisionEntry], amend: bool) -> BackendResult<()>;
+    // same as 'status' but also passes the entries along as they are parsed
+    fn status_incremental(&self, on_entries: &mut dyn FnMut(Vec<RevisionEntry>)) -> BackendResult<StatusInfo>;
+    fn stage(&self, entries: &[RevisionEntry]) -> BackendResult<()>;
+    fn stage_tracked(&self) -> BackendResult<()>;
+    fn unstage(&self, entries: &[RevisionEntry]) -> BackendResult<()>;
+    // 'None' entries commit only what is already staged
+    fn commit(&self, message: &str, entries: Option<&[RevisionEntry]>, amend: bool, date: Option<&str>) -> BackendResult<()>;
+    fn commit_empty(&self, message: &str) -> BackendResult<()>;
+    fn commit_in_editor(&self, entries: Option<&[RevisionEntry]>, verbose: bool) -> BackendResult<()>;
     fn discard(&self, entries: &[RevisionEntry]) -> BackendResult<()>;
-    fn diff(&self, revision: Option<&str>, entries: &[RevisionEntry]) -> BackendResult<String>;
+    fn remove(&self, entries: &[RevisionEntry], keep_on_disk: bool) -> BackendResult<()>;
+    fn move_file(&self, from: &str, to: &str) -> BackendResult<()>;
+    fn diff(&self, revision: Option<&str>, entries: &[RevisionEntry], options: &DiffOptions) -> BackendResult<String>;
+    fn diff_staged(&self, options: &DiffOptions) -> BackendResult<String>;
+    fn diff_merge_base(&self, branch: &str, options: &DiffOptions) -> BackendResult<String>;
+    fn diff_parent(
+        &self,
+        revision: &str,
+        parent: Option<usize>,
+        entries: &[RevisionEntry],
+        options: &DiffOptions,
+    ) -> BackendResult<String>;
+    fn diff_renames(&self, revision: Option<&str>, entries: &[RevisionEntry], options: &DiffOptions)
+        -> BackendResult<String>;
+    fn continue_operation(&self, operation: PendingOperation) -> BackendResult<()>;
+    fn abort_operation(&self, operation: PendingOperation) -> BackendResult<()>;
+    fn rerere(&self) -> BackendResult<()>;
+    fn rerere_forget(&self, entries: &[RevisionEntry]) -> BackendResult<()>;
     fn resolve_taking_ours(&self, entries: &[RevisionEntry]) -> BackendResult<()>;
     fn resolve_taking_theirs(&self, entries: &[RevisionEntry]) -> BackendResult<()>;
+    fn stage_patch(&self, patch: &str) -> BackendResult<()>;
+    fn has_staged_changes(&self) -> BackendResult<bool>;
+    fn diff_stat(&self, diff: &str) -> BackendResult<Vec<DiffStatEntry>>;
+    fn apply_patch(&self, path: &str) -> BackendResult<()>;
 
-    fn log(&self, start: usize, len: usize) -> BackendResult<(usize, Vec<LogEntry>)>;
+    fn log(
+        &self,
+        start: usize,
+        len: usize,
+        all: bool,
+        no_merges: bool,
+        first_parent: bool,
+    ) -> BackendResult<(usize, Vec<LogEntry>)>;
+    fn log_pickaxe(&self, start: usize, len: usize, term: &str, regex: bool) -> BackendResult<(usize, Vec<LogEntry>)>;
     fn checkout(&self, revision: &str) -> BackendResult<()>;
-    fn merge(&self, revision: &str) -> BackendResult<()>;
-    fn fetch(&self) -> BackendResult<()>;
-    fn pull(&self) -> BackendResult<()>;
-    fn push(&self) -> BackendResult<()>;
-    fn push_gerrit(&self) -> BackendResult<()>;
+    // these return what git printed, warnings included
+    fn merge(&self, revision: &str, strategy: MergeStrategy) -> BackendResult<String>;
+    // also returns the remote branches it pruned
+    fn fetch(&self) -> BackendResult<(String, Vec<String>)>;
+    fn pull(&self) -> BackendResult<String>;
+    fn push(&self) -> BackendResult<String>;
+    fn push_gerrit(&self) -> BackendResult<String>;
+    fn push_preview(&self) -> BackendResult<String>;
+    fn current_branch(&self) -> BackendResult<String>;
+    fn head_is_detached(&self) -> BackendResult<bool>;
+    fn remote(&self) -> BackendResult<String>;
+    fn head_is_pushed(&self) -> BackendResult<bool>;
     fn reset(&self, revision: &str) -> BackendResult<()>;
+    fn log_range(&self, from: &str, to: &str) -> BackendResult<Vec<LogEntry>>;
+    fn rebase_todo(&self, base: &str) -> BackendResult<Vec<RebaseEntry>>;
+    fn rebase_interactive(&self, base: &str, entries: &[RebaseEntry], in_terminal: bool) -> BackendResult<()>;
+    fn reword(&self, revision: &str, message: &str) -> BackendResult<()>;
+    fn gc(&self) -> BackendResult<()>;
 
-    fn stash(&self, message: &str, entries: &[RevisionEntry]) -> BackendResult<()>;
+    fn stash(&self, message: &str, entries: &[RevisionEntry], include_untracked: bool) -> BackendResult<()>;
     fn stash_list(&self) -> BackendResult<Vec<StashEntry>>;
     fn stash_pop(&self, id: usize) -> BackendResult<()>;
     fn stash_show(&self, id: usize) -> BackendResult<String>;
-    fn stash_diff(&self, id: usize) -> BackendResult<String>;
+    fn stash_files(&self, id: usize) -> BackendResult<Vec<RevisionEntry>>;
+    fn stash_diff(&self, id: usize, entries: &[RevisionEntry], options: &DiffOptions) -> BackendResult<String>;
+    fn diff_stash_working_tree(&self, id: usize, entries: &[RevisionEntry], options: &DiffOptions) -> BackendResult<String>;
     fn stash_drop(&self, id: usize) -> BackendResult<()>;
 
     fn revision_details(&self, revision: &str) -> BackendResult<RevisionInfo>;
+    fn notes_show(&self, revision: &str) -> BackendResult<String>;
+    fn notes_add(&self, revision: &str, note: &str) -> BackendResult<()>;
+    fn restore_file_from(&self, revision: &str, path: &str) -> BackendResult<()>;
+    fn show_file(&self, revision: &str, path: &str) -> BackendResult<String>;
+    fn working_file(&self, path: &str) -> BackendResult<String>;
+    fn blame(&self, revision: &str, path: &str) -> BackendResult<Vec<BlameEntry>>;
 
-    fn branches(&self) -> BackendResult<Vec<BranchEntry>>;
+    fn branches(&self, include_remotes: bool) -> BackendResult<Vec<BranchEntry>>;
+    fn checkout_tracking(&self, remote_branch: &str) -> BackendResult<()>;
     fn new_branch(&self, name: &str) -> BackendResult<()>;
     fn delete_branch(&self, name: &str, force: bool) -> BackendResult<()>;
+    fn delete_remote_branch(&self, remote_branch: &str) -> BackendResult<()>;
+    fn set_upstream(&self, name: &str, upstream: &str) -> BackendResult<()>;
 
     fn tags(&self) -> BackendResult<Vec<TagEntry>>;
-    fn new_tag(&self, name: &str) -> BackendResult<()>;
+    fn new_tag(&self, name: &str, signed_message: Option<&str>) -> BackendResult<()>; // lightweight unless signed
+    fn verify_tag(&self, name: &str) -> BackendResult<String>;
     fn delete_tag(&self, name: &str) -> BackendResult<()>;
+
+    fn undo_description(&self) -> Option<String>;
+    fn undo(&self) -> BackendResult<()>;
+}
+
+const COMMAND_HISTORY_MAX_LEN: usize = 256;
+static COMMAND_HISTORY: Mutex<VecDeque<CommandEntry>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone, Debug)]
+pub struct CommandEntry {
+    pub command: String,
+    pub success: bool,
+}
+impl FilterEntry for CommandEntry {
+    fn fuzzy_matches(&self, pattern: &str) -> bool {
+        fuzzy_matches(&self.command, pattern)
+    }
+}
+
+fn push_command_history(command: String, success: bool) {
+    let mut history = COMMAND_HISTORY.lock().unwrap();
+    if history.len() == COMMAND_HISTORY_MAX_LEN {
+        history.pop_front();
+    }
+    history.push_back(CommandEntry { command, success });
+}
+
+pub fn command_history() -> Vec<CommandEntry> {
+    COMMAND_HISTORY.lock().unwrap().iter().cloned().collect()
+}
+
+// in seconds, zero means processes are waited for as long as they run
+static PROCESS_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_process_timeout(timeout: Option<Duration>) {
+    PROCESS_TIMEOUT.store(timeout.map(|t| t.as_secs()).unwrap_or(0), Ordering::Relaxed);
 }
 
-pub struct Process(Child);
+fn process_timeout() -> Option<Duration> {
+    match PROCESS_TIMEOUT.load(Ordering::Relaxed) {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    }
+}
+
+pub struct Process {
+    child: Child,
+    command: String,
+    timeout: Option<Duration>,
+}
 impl Process {
     pub fn spawn(command_name: &str, args: &[&str]) -> BackendResult<Self> {
+        Self::spawn_with_env(command_name, args, &[])
+    }
+
+    pub fn spawn_with_env(command_name: &str, args: &[&str], env: &[(&str, &str)]) -> BackendResult<Self> {
+        Self::spawn_with_stdio(command_name, args, env, Stdio::null(), Stdio::piped(), Stdio::piped())
+    }
+
+    // for interactive processes (an editor for example) that need the terminal
+    pub fn spawn_in_terminal(command_name: &str, args: &[&str], env: &[(&str, &str)]) -> BackendResult<Self> {
+        let mut process =
+            Self::spawn_with_stdio(command_name, args, env, Stdio::inherit(), Stdio::inherit(), Stdio::inherit())?;
+        // the user might take a while in there
+        process.timeout = None;
+        Ok(process)
+    }
+
+    pub fn spawn_with_input(command_name: &str, args: &[&str], input: &[u8]) -> BackendResult<Self> {
+        let mut process = Self::spawn_with_stdio(command_name, args, &[], Stdio::piped(), Stdio::piped(), Stdio::piped())?;
+        if let Some(mut stdin) = process.child.stdin.take() {
+            if let Err(error) = stdin.write_all(input) {
+                return Err(format!("could not write to process '{}': {}", process.command, error));
+            }
+        }
+        Ok(process)
+    }
+
+    fn spawn_with_stdio(
+        command_name: &str,
+        args: &[&str],
+        env: &[(&str, &str)],
+        stdin: Stdio,
+        stdout: Stdio,
+        stderr: Stdio,
+    ) -> BackendResult<Self> {
+        let mut command_text = String::new();
+        for (key, value) in env {
+            command_text.push_str(key);
+            command_text.push('=');
+            command_text.push_str(value);
+            command_text.push(' ');
+        }
+        command_text.push_str(command_name);
+        for arg in args {
+            command_text.push(' ');
+            command_text.push_str(arg);
+        }
+
         let mut command = Command::new(command_name);
-        command.args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        command.args(args).envs(env.iter().copied()).stdin(stdin).stdout(stdout).stderr(stderr);
 
         match command.spawn() {
-            Ok(child) => Ok(Self(child)),
-            Err(error) => Err(format!("could not spawn process '{}': {}", command_name, error)),
+            Ok(child) => Ok(Self { child, command: command_text, timeout: process_timeout() }),
+            Err(error) => {
+                push_command_history(command_text, false);
+                Err(format!("could not spawn process '{}': {}", command_name, error))
+            }
         }
     }
 
     pub fn wait(self) -> BackendResult<String> {
-        let output = match self.0.wait_with_output() {
-            Ok(output) => output,
-            Err(error) => return Err(format!("could not wait for process: {}", error)),
+        self.wait_output(false)
+    }
+
+    // hands stdout over as it is read instead of collecting it
+    pub fn wait_streaming(mut self, on_output: &mut dyn FnMut(&[u8])) -> BackendResult<()> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let stderr = read_to_end(self.child.stderr.take());
+        if let Some(mut stdout) = self.child.stdout.take() {
+            // read on its own thread so the deadline still applies while the child prints nothing
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut buf = [0; 8 * 1024];
+                loop {
+                    match stdout.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(len) => {
+                            if sender.send(buf[..len].to_vec()).is_err() {
+                                break;
+                            }
+                        }
+                        Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                        Err(_) => break,
+                    }
+                }
+            });
+
+            loop {
+                let chunk = match deadline {
+                    Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match chunk {
+                    Ok(chunk) => on_output(&chunk),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                    Err(RecvTimeoutError::Timeout) => {
+                        let _ = self.child.kill();
+                        let _ = self.child.wait();
+                        return Err(Self::timed_out(self.command, self.timeout));
+                    }
+                }
+            }
+        }
+
+        let (command, mut output) = self.wait_for_exit(deadline)?;
+        output.stderr = stderr.join().unwrap_or_default();
+        let success = output.status.success();
+        let result = if success { Ok(()) } else { Err(failure_text(&command, &output)) };
+        push_command_history(command, success);
+        result
+    }
+
+    // also keeps stderr on success, for commands that report there (gpg verification for example)
+    pub fn wait_with_stderr(self) -> BackendResult<String> {
+        self.wait_output(true)
+    }
+
+    // for commands that answer with their exit code, 1 meaning no instead of a failure
+    pub fn wait_answer(self) -> BackendResult<bool> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let (command, output) = self.wait_for_exit(deadline)?;
+        let answer = match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(failure_text(&command, &output)),
         };
+        push_command_history(command, answer.is_ok());
+        answer
+    }
+
+    fn wait_output(self, include_stderr: bool) -> BackendResult<String> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.wait_until(include_stderr, deadline)
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if output.status.success() {
-            Ok(stdout.into())
+    fn wait_until(self, include_stderr: bool, deadline: Option<Instant>) -> BackendResult<String> {
+        let (command, output) = self.wait_for_exit(deadline)?;
+
+        let success = output.status.success();
+        let result = if success {
+            let mut text: String = String::from_utf8_lossy(&output.stdout).into();
+            if include_stderr {
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+            }
+            Ok(text)
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let mut error = String::new();
-            error.push_str(&stdout);
-            error.push('\n');
-            error.push_str(&stderr);
-            Err(error)
+            Err(failure_text(&command, &output))
+        };
+
+        push_command_history(command, success);
+        result
+    }
+
+    // the command text is handed back for the history, which records failures to wait right away
+    fn wait_for_exit(self, deadline: Option<Instant>) -> BackendResult<(String, Output)> {
+        let output = match deadline {
+            Some(deadline) => wait_with_deadline(self.child, deadline),
+            None => self.child.wait_with_output().map(Some),
+        };
+        match output {
+            Ok(Some(output)) => Ok((self.command, output)),
+            Ok(None) => Err(Self::timed_out(self.command, self.timeout)),
+            Err(error) => {
+                let error = format!("could not wait for process '{}': {}", self.command, error);
+                push_command_history(self.command, false);
+                Err(error)
+            }
+        }
+    }
+
+    fn timed_out(command: String, timeout: Option<Duration>) -> String {
+        let error = format!("{}:\noperation timed out after {} seconds", command, timeout.unwrap_or_default().as_secs());
+        push_command_history(command, false);
+        error
+    }
+}
+
+fn failure_text(command: &str, output: &Output) -> String {
+    format!("{}:\n{}\n{}", command, String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+}
+
+// returns None if the child got killed for still running after 'deadline'
+// pipes are drained on their own threads so a full one does not stall the child
+fn read_to_end<R: 'static + Send + Read>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+fn wait_with_deadline(mut child: Child, deadline: Instant) -> io::Result<Option<Output>> {
+    let stdout = read_to_end(child.stdout.take());
+    let stderr = read_to_end(child.stderr.take());
+    loop {
+        if let Some(status) = child.try_wait()? {
+            let stdout = stdout.join().unwrap_or_default();
+            let stderr = stderr.join().unwrap_or_default();
+            return Ok(Some(Output { status, stdout, stderr }));
+        }
+        if Instant::now() >= deadline {
+            // the readers are left alone as processes spawned by the child may keep the pipes open
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
         }
+        thread::sleep(Duration::from_millis(20));
     }
 }
 
-pub fn backend_from_current_repository() -> Option<(PathBuf, Arc<dyn Backend>)> {
-    if let Some((root, git)) = git::Git::try_new() {
+pub fn backend_from_current_repository(git_path: &str) -> Option<(PathBuf, Arc<dyn Backend>)> {
+    if let Some((root, git)) = git::Git::try_new(git_path) {
         Some((root, Arc::new(git)))
     } else {
         None
@@ -0,0 +1,389 @@
+use std::sync::Mutex;
+
+use crate::backend::{
+    Backend, BackendResult, BlameEntry, BranchEntry, DiffOptions, DiffStatEntry, LogEntry, MergeStrategy, PendingOperation,
+    RebaseEntry, RevisionEntry, RevisionInfo, StashEntry, StatusInfo, TagEntry,
+};
+
+// backend with canned responses so modes can be tested without a repository
+#[derive(Default)]
+pub struct FakeBackend {
+    pub status_header: String,
+    pub status_entries: Vec<RevisionEntry>,
+    pub log_entries: Vec<LogEntry>,
+    pub revision_info: RevisionInfo,
+    pub branches: Vec<BranchEntry>,
+    pub tags: Vec<TagEntry>,
+    pub stashes: Vec<StashEntry>,
+    pub rebase_entries: Vec<RebaseEntry>,
+    pub pending_operation: Option<PendingOperation>,
+    pub head_pushed: bool,
+    pub diff_stat_entries: Vec<DiffStatEntry>,
+    pub output: String,
+    // when set, every call fails with it
+    pub error: Option<String>,
+    // when set, only calls with this name fail with 'error'
+    pub failing_call: Option<&'static str>,
+    pub calls: Mutex<Vec<String>>,
+}
+impl FakeBackend {
+    // names of the methods called so far, in order
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn call(&self, name: &str) -> BackendResult<()> {
+        self.calls.lock().unwrap().push(name.into());
+        match &self.error {
+            Some(error) if self.failing_call.is_none_or(|failing| failing == name) => Err(error.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    fn output(&self, name: &str) -> BackendResult<String> {
+        self.call(name)?;
+        Ok(self.output.clone())
+    }
+}
+
+impl Backend for FakeBackend {
+    fn status(&self) -> BackendResult<StatusInfo> {
+        self.call("status")?;
+        Ok(StatusInfo {
+            header: self.status_header.clone(),
+            entries: self.status_entries.clone(),
+            pending_operation: self.pending_operation,
+        })
+    }
+
+    fn status_incremental(&self, on_entries: &mut dyn FnMut(Vec<RevisionEntry>)) -> BackendResult<StatusInfo> {
+        let info = self.status()?;
+        on_entries(info.entries.clone());
+        Ok(info)
+    }
+
+    fn stage(&self, _entries: &[RevisionEntry]) -> BackendResult<()> {
+        self.call("stage")
+    }
+
+    fn stage_tracked(&self) -> BackendResult<()> {
+        self.call("stage_tracked")
+    }
+
+    fn unstage(&self, _entries: &[RevisionEntry]) -> BackendResult<()> {
+        self.call("unstage")
+    }
+
+    fn commit(
+        &self,
+        _message: &str,
+        _entries: Option<&[RevisionEntry]>,
+        _amend: bool,
+        _date: Option<&str>,
+    ) -> BackendResult<()> {
+        self.call("commit")
+    }
+
+    fn commit_empty(&self, _message: &str) -> BackendResult<()> {
+        self.call("commit_empty")
+    }
+
+    fn commit_in_editor(&self, _entries: Option<&[RevisionEntry]>, _verbose: bool) -> BackendResult<()> {
+        self.call("commit_in_editor")
+    }
+
+    fn discard(&self, _entries: &[RevisionEntry]) -> BackendResult<()> {
+        self.call("discard")
+    }
+
+    fn remove(&self, _entries: &[RevisionEntry], _keep_on_disk: bool) -> BackendResult<()> {
+        self.call("remove")
+    }
+
+    fn move_file(&self, _from: &str, _to: &str) -> BackendResult<()> {
+        self.call("move_file")
+    }
+
+    fn diff(&self, _revision: Option<&str>, _entries: &[RevisionEntry], _options: &DiffOptions) -> BackendResult<String> {
+        self.output("diff")
+    }
+
+    fn diff_staged(&self, _options: &DiffOptions) -> BackendResult<String> {
+        self.output("diff_staged")
+    }
+
+    fn diff_merge_base(&self, _branch: &str, _options: &DiffOptions) -> BackendResult<String> {
+        self.output("diff_merge_base")
+    }
+
+    fn diff_parent(
+        &self,
+        _revision: &str,
+        _parent: Option<usize>,
+        _entries: &[RevisionEntry],
+        _options: &DiffOptions,
+    ) -> BackendResult<String> {
+        self.output("diff_parent")
+    }
+
+    fn diff_renames(
+        &self,
+        _revision: Option<&str>,
+        _entries: &[RevisionEntry],
+        _options: &DiffOptions,
+    ) -> BackendResult<String> {
+        self.output("diff_renames")
+    }
+
+    fn continue_operation(&self, _operation: PendingOperation) -> BackendResult<()> {
+        self.call("continue_operation")
+    }
+
+    fn abort_operation(&self, _operation: PendingOperation) -> BackendResult<()> {
+        self.call("abort_operation")
+    }
+
+    fn rerere(&self) -> BackendResult<()> {
+        self.call("rerere")
+    }
+
+    fn rerere_forget(&self, _entries: &[RevisionEntry]) -> BackendResult<()> {
+        self.call("rerere_forget")
+    }
+
+    fn resolve_taking_ours(&self, _entries: &[RevisionEntry]) -> BackendResult<()> {
+        self.call("resolve_taking_ours")
+    }
+
+    fn resolve_taking_theirs(&self, _entries: &[RevisionEntry]) -> BackendResult<()> {
+        self.call("resolve_taking_theirs")
+    }
+
+    fn stage_patch(&self, _patch: &str) -> BackendResult<()> {
+        self.call("stage_patch")
+    }
+
+    fn has_staged_changes(&self) -> BackendResult<bool> {
+        self.call("has_staged_changes")?;
+        Ok(false)
+    }
+
+    fn diff_stat(&self, _diff: &str) -> BackendResult<Vec<DiffStatEntry>> {
+        self.call("diff_stat")?;
+        Ok(self.diff_stat_entries.clone())
+    }
+
+    fn apply_patch(&self, _path: &str) -> BackendResult<()> {
+        self.call("apply_patch")
+    }
+
+    fn log(
+        &self,
+        start: usize,
+        len: usize,
+        _all: bool,
+        _no_merges: bool,
+        _first_parent: bool,
+    ) -> BackendResult<(usize, Vec<LogEntry>)> {
+        self.call("log")?;
+        let entries = self.log_entries.iter().skip(start).take(len).cloned().collect();
+        Ok((start, entries))
+    }
+
+    fn log_pickaxe(&self, start: usize, len: usize, _term: &str, _regex: bool) -> BackendResult<(usize, Vec<LogEntry>)> {
+        self.call("log_pickaxe")?;
+        let entries = self.log_entries.iter().skip(start).take(len).cloned().collect();
+        Ok((start, entries))
+    }
+
+    fn checkout(&self, _revision: &str) -> BackendResult<()> {
+        self.call("checkout")
+    }
+
+    fn merge(&self, _revision: &str, _strategy: MergeStrategy) -> BackendResult<String> {
+        self.output("merge")
+    }
+
+    fn fetch(&self) -> BackendResult<(String, Vec<String>)> {
+        Ok((self.output("fetch")?, Vec::new()))
+    }
+
+    fn pull(&self) -> BackendResult<String> {
+        self.output("pull")
+    }
+
+    fn push(&self) -> BackendResult<String> {
+        self.output("push")
+    }
+
+    fn push_gerrit(&self) -> BackendResult<String> {
+        self.output("push_gerrit")
+    }
+
+    fn push_preview(&self) -> BackendResult<String> {
+        self.output("push_preview")
+    }
+
+    fn current_branch(&self) -> BackendResult<String> {
+        self.output("current_branch")
+    }
+
+    fn head_is_detached(&self) -> BackendResult<bool> {
+        self.call("head_is_detached")?;
+        Ok(false)
+    }
+
+    fn remote(&self) -> BackendResult<String> {
+        self.output("remote")
+    }
+
+    fn head_is_pushed(&self) -> BackendResult<bool> {
+        self.call("head_is_pushed")?;
+        Ok(self.head_pushed)
+    }
+
+    fn reset(&self, _revision: &str) -> BackendResult<()> {
+        self.call("reset")
+    }
+
+    fn log_range(&self, _from: &str, _to: &str) -> BackendResult<Vec<LogEntry>> {
+        self.call("log_range")?;
+        Ok(self.log_entries.clone())
+    }
+
+    fn rebase_todo(&self, _base: &str) -> BackendResult<Vec<RebaseEntry>> {
+        self.call("rebase_todo")?;
+        Ok(self.rebase_entries.clone())
+    }
+
+    fn rebase_interactive(&self, _base: &str, _entries: &[RebaseEntry], _in_terminal: bool) -> BackendResult<()> {
+        self.call("rebase_interactive")
+    }
+
+    fn reword(&self, _revision: &str, _message: &str) -> BackendResult<()> {
+        self.call("reword")
+    }
+
+    fn gc(&self) -> BackendResult<()> {
+        self.call("gc")
+    }
+
+    fn stash(&self, _message: &str, _entries: &[RevisionEntry], _include_untracked: bool) -> BackendResult<()> {
+        self.call("stash")
+    }
+
+    fn stash_list(&self) -> BackendResult<Vec<StashEntry>> {
+        self.call("stash_list")?;
+        Ok(self.stashes.clone())
+    }
+
+    fn stash_pop(&self, _id: usize) -> BackendResult<()> {
+        self.call("stash_pop")
+    }
+
+    fn stash_show(&self, _id: usize) -> BackendResult<String> {
+        self.output("stash_show")
+    }
+
+    fn stash_files(&self, _id: usize) -> BackendResult<Vec<RevisionEntry>> {
+        self.call("stash_files")?;
+        Ok(self.status_entries.clone())
+    }
+
+    fn diff_stash_working_tree(
+        &self,
+        _id: usize,
+        _entries: &[RevisionEntry],
+        _options: &DiffOptions,
+    ) -> BackendResult<String> {
+        self.output("diff_stash_working_tree")
+    }
+
+    fn stash_diff(&self, _id: usize, _entries: &[RevisionEntry], _options: &DiffOptions) -> BackendResult<String> {
+        self.output("stash_diff")
+    }
+
+    fn stash_drop(&self, _id: usize) -> BackendResult<()> {
+        self.call("stash_drop")
+    }
+
+    fn revision_details(&self, _revision: &str) -> BackendResult<RevisionInfo> {
+        self.call("revision_details")?;
+        Ok(self.revision_info.clone())
+    }
+
+    fn notes_show(&self, _revision: &str) -> BackendResult<String> {
+        self.output("notes_show")
+    }
+
+    fn notes_add(&self, _revision: &str, _note: &str) -> BackendResult<()> {
+        self.call("notes_add")
+    }
+
+    fn restore_file_from(&self, _revision: &str, _path: &str) -> BackendResult<()> {
+        self.call("restore_file_from")
+    }
+
+    fn show_file(&self, _revision: &str, _path: &str) -> BackendResult<String> {
+        self.output("show_file")
+    }
+
+    fn working_file(&self, _path: &str) -> BackendResult<String> {
+        self.output("working_file")
+    }
+
+    fn blame(&self, _revision: &str, _path: &str) -> BackendResult<Vec<BlameEntry>> {
+        self.call("blame")?;
+        Ok(Vec::new())
+    }
+
+    fn branches(&self, include_remotes: bool) -> BackendResult<Vec<BranchEntry>> {
+        self.call("branches")?;
+        Ok(self.branches.iter().filter(|b| include_remotes || !b.remote).cloned().collect())
+    }
+
+    fn checkout_tracking(&self, _remote_branch: &str) -> BackendResult<()> {
+        self.call("checkout_tracking")
+    }
+
+    fn new_branch(&self, _name: &str) -> BackendResult<()> {
+        self.call("new_branch")
+    }
+
+    fn delete_branch(&self, _name: &str, _force: bool) -> BackendResult<()> {
+        self.call("delete_branch")
+    }
+
+    fn delete_remote_branch(&self, _remote_branch: &str) -> BackendResult<()> {
+        self.call("delete_remote_branch")
+    }
+
+    fn set_upstream(&self, _name: &str, _upstream: &str) -> BackendResult<()> {
+        self.call("set_upstream")
+    }
+
+    fn tags(&self) -> BackendResult<Vec<TagEntry>> {
+        self.call("tags")?;
+        Ok(self.tags.clone())
+    }
+
+    fn new_tag(&self, _name: &str, _signed_message: Option<&str>) -> BackendResult<()> {
+        self.call("new_tag")
+    }
+
+    fn verify_tag(&self, _name: &str) -> BackendResult<String> {
+        self.output("verify_tag")
+    }
+
+    fn delete_tag(&self, _name: &str) -> BackendResult<()> {
+        self.call("delete_tag")
+    }
+
+    fn undo_description(&self) -> Option<String> {
+        None
+    }
+
+    fn undo(&self) -> BackendResult<()> {
+        self.call("undo")
+    }
+}
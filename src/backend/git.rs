@@ -1,30 +1,133 @@
-use std::path::{Path, PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
 use super::{
-    Backend, BackendResult, BranchEntry, FileStatus, LogEntry, Process, RevisionEntry, RevisionInfo, StashEntry, StatusInfo,
-    TagEntry,
+    Backend, BackendResult, BlameEntry, BranchEntry, DiffOptions, DiffStatEntry, FileStatus, LogEntry, MergeStrategy,
+    PendingOperation, Process, RebaseAction, RebaseEntry, RevisionEntry, RevisionInfo, StashEntry, StatusInfo, TagEntry,
 };
 
 //use crate::tool;
 
-pub struct Git;
+enum UndoPoint {
+    ResetTo(String),
+    ApplyPatch(String),
+}
+
+pub struct Git {
+    git: String, // path to the git executable
+    git_dir: PathBuf,
+    main_worktree: Option<PathBuf>, // only set inside a linked worktree
+    undo_point: Mutex<Option<UndoPoint>>,
+}
 
 impl Git {
-    pub fn try_new() -> Option<(PathBuf, Self)> {
-        let output = Process::spawn("git", &["rev-parse", "--show-toplevel"]).ok()?.wait().ok()?;
+    pub fn try_new(git: &str) -> Option<(PathBuf, Self)> {
+        let output = Process::spawn(git, &["rev-parse", "--absolute-git-dir"]).ok()?.wait().ok()?;
+        let git_dir: PathBuf = Path::new(output.trim()).into();
+
+        // bare repositories and a GIT_DIR without GIT_WORK_TREE have no work tree
+        let work_tree = Process::spawn(git, &["rev-parse", "--show-toplevel"])
+            .and_then(Process::wait)
+            .ok()
+            .map(|output| PathBuf::from(output.trim()))
+            .filter(|path| !path.as_os_str().is_empty());
+
+        // keeps a relative GIT_DIR and GIT_WORK_TREE valid after the current dir changes to the root
+        if env::var_os("GIT_DIR").is_some() {
+            env::set_var("GIT_DIR", &git_dir);
+            if let Some(work_tree) = &work_tree {
+                env::set_var("GIT_WORK_TREE", work_tree);
+            }
+        }
+
+        // every command runs from the root, which for a linked worktree is its own dir and not the
+        // main repository's, so only the latter needs to be looked up
+        let main_worktree = work_tree.as_ref().and_then(|work_tree| {
+            let output = Process::spawn(git, &["worktree", "list", "--porcelain"]).and_then(Process::wait).ok()?;
+            let main_worktree = PathBuf::from(output.lines().next()?.strip_prefix("worktree ")?);
+            Some(main_worktree).filter(|main_worktree| main_worktree != work_tree)
+        });
 
-        let root = Path::new(output.trim()).into();
-        Some((root, Self))
+        let root = work_tree.unwrap_or_else(|| git_dir.clone());
+        Some((root, Self { git: git.into(), git_dir, main_worktree, undo_point: Mutex::new(None) }))
     }
 
-    fn remote(&self) -> BackendResult<String> {
-        let remote = Process::spawn("git", &["remote"])?.wait()?.trim().to_owned();
-        Ok(remote)
+    fn set_undo_point(&self, undo_point: Option<UndoPoint>) {
+        *self.undo_point.lock().unwrap() = undo_point;
     }
 
-    fn current_branch(&self) -> BackendResult<String> {
-        let branch = Process::spawn("git", &["symbolic-ref", "--short", "HEAD"])?.wait()?.trim().to_owned();
-        Ok(branch)
+    fn record_head_undo_point(&self) {
+        let head = Process::spawn(&self.git, &["rev-parse", "HEAD"]).and_then(Process::wait);
+        self.set_undo_point(head.ok().map(|head| UndoPoint::ResetTo(head.trim().into())));
+    }
+
+    // a file inside the git dir, used to pass files in and out of git's editors
+    fn git_path(&self, name: &str) -> String {
+        self.git_dir.join(name).to_string_lossy().into()
+    }
+
+    // without the setting, rerere is on as long as its cache exists
+    fn rerere_enabled(&self) -> bool {
+        match Process::spawn(&self.git, &["config", "--type=bool", "rerere.enabled"]).and_then(Process::wait) {
+            Ok(enabled) => enabled.trim() == "true",
+            Err(_) => Process::spawn(&self.git, &["rev-parse", "--git-path", "rr-cache"])
+                .and_then(Process::wait)
+                .map(|path| Path::new(path.trim()).is_dir())
+                .unwrap_or(false),
+        }
+    }
+
+    // a rebase can also leave 'CHERRY_PICK_HEAD' behind when one of its picks conflicts, so it is checked first
+    fn pending_operation(&self) -> Option<PendingOperation> {
+        let exists = |name| self.git_dir.join(name).exists();
+        if exists("rebase-merge") || exists("rebase-apply") {
+            Some(PendingOperation::Rebase)
+        } else if exists("MERGE_HEAD") {
+            Some(PendingOperation::Merge)
+        } else if exists("CHERRY_PICK_HEAD") {
+            Some(PendingOperation::CherryPick)
+        } else if exists("REVERT_HEAD") {
+            Some(PendingOperation::Revert)
+        } else if exists("BISECT_LOG") {
+            Some(PendingOperation::Bisect)
+        } else {
+            None
+        }
+    }
+
+    fn run_rebase(&self, base: &str, entries: &[RebaseEntry], env: &[(&str, &str)], in_terminal: bool) -> BackendResult<()> {
+        let mut todo = String::new();
+        for entry in entries {
+            todo.push_str(entry.action.as_str());
+            // commands like 'break' take no arguments at all
+            for arg in [&entry.hash, &entry.message].iter().filter(|arg| !arg.is_empty()) {
+                todo.push(' ');
+                todo.push_str(arg);
+            }
+            todo.push('\n');
+        }
+
+        let path = self.git_path("verco-rebase-todo");
+        fs::write(&path, todo).map_err(|e| format!("could not write the rebase todo list: {}", e))?;
+
+        // git appends the path of its todo list so this replaces it with ours
+        let editor = format!("cp {}", shell_quote(&path));
+        let mut env = env.to_vec();
+        env.push(("GIT_SEQUENCE_EDITOR", &editor));
+
+        let process = if in_terminal {
+            Process::spawn_in_terminal(&self.git, &["rebase", "-i", base], &env)
+        } else {
+            Process::spawn_with_env(&self.git, &["rebase", "-i", base], &env)
+        };
+        let result = process.and_then(Process::wait);
+
+        let _ = fs::remove_file(&path);
+        result?;
+        Ok(())
     }
 
     fn remote_branch(&self) -> BackendResult<String> {
@@ -38,52 +141,134 @@ impl Git {
 
 impl Backend for Git {
     fn status(&self) -> BackendResult<StatusInfo> {
-        let output = Process::spawn("git", &["status", "--branch", "--no-rename", "--null"])?.wait()?;
-        let mut splits = output.split('\0').map(str::trim);
-
-        let header = splits.next().unwrap_or("").into();
-        let entries = splits
-            .filter(|e| e.len() >= 2)
-            .map(|e| {
-                let (status, filename) = e.split_at(2);
-                RevisionEntry::new(filename.trim().into(), parse_file_status(status))
-            })
-            .collect();
+        self.status_incremental(&mut |_| ())
+    }
+
+    fn status_incremental(&self, on_entries: &mut dyn FnMut(Vec<RevisionEntry>)) -> BackendResult<StatusInfo> {
+        let args = ["status", "--porcelain=v2", "--branch", "--no-renames", "-z"];
+        let mut branch = BranchStatus::default();
+        let mut entries = Vec::new();
+        let mut pending = Vec::new();
 
-        Ok(StatusInfo { header, entries })
+        Process::spawn(&self.git, &args)?.wait_streaming(&mut |output| {
+            pending.extend_from_slice(output);
+
+            // only whole records, the rest waits for the next read
+            let mut parsed = Vec::new();
+            while let Some(end) = pending.iter().position(|&b| b == b'\0') {
+                let record: Vec<_> = pending.drain(..=end).collect();
+                let record = String::from_utf8_lossy(&record[..end]);
+                match record.strip_prefix("# ") {
+                    Some(header) => branch.parse(header),
+                    None => parsed.extend(parse_status_record(&record)),
+                }
+            }
+
+            if !parsed.is_empty() {
+                entries.extend(parsed.iter().cloned());
+                on_entries(parsed);
+            }
+        })?;
+
+        // what rerere could not resolve from a recorded resolution is still remaining
+        if entries.iter().any(|e| matches!(e.status, FileStatus::Unmerged)) && self.rerere_enabled() {
+            let remaining = Process::spawn(&self.git, &["rerere", "remaining"]).and_then(Process::wait).unwrap_or_default();
+            for entry in entries.iter_mut().filter(|e| matches!(e.status, FileStatus::Unmerged)) {
+                entry.rerere_resolved = !remaining.lines().any(|l| l == entry.name);
+            }
+        }
+
+        let mut header = branch.header();
+        if let Some(main_worktree) = &self.main_worktree {
+            header.push_str(&format!(", linked worktree of {}", main_worktree.display()));
+        }
+        Ok(StatusInfo { header, entries, pending_operation: self.pending_operation() })
     }
 
-    fn commit(&self, message: &str, entries: &[RevisionEntry], amend: bool) -> BackendResult<()> {
+    fn stage(&self, entries: &[RevisionEntry]) -> BackendResult<()> {
         if entries.is_empty() {
-            Process::spawn("git", &["add", "--all"])?.wait()?;
+            Process::spawn(&self.git, &["add", "--all"])?.wait()?;
         } else {
             let mut args = vec!["add", "--"];
             for entry in entries {
                 args.push(&entry.name);
             }
 
-            Process::spawn("git", &args)?.wait()?;
+            Process::spawn(&self.git, &args)?.wait()?;
         }
+        Ok(())
+    }
 
-        if amend {
-            Process::spawn("git", &["commit", "--amend", "--no-edit"])?.wait()?;
+    // leaves untracked files out, unlike 'add --all'
+    fn stage_tracked(&self) -> BackendResult<()> {
+        Process::spawn(&self.git, &["add", "--update"])?.wait()?;
+        Ok(())
+    }
+
+    fn unstage(&self, entries: &[RevisionEntry]) -> BackendResult<()> {
+        // unlike 'reset', 'restore' also works before the first commit
+        let mut args = vec!["restore", "--staged", "--"];
+        if entries.is_empty() {
+            args.push(":/");
         } else {
-            Process::spawn("git", &["commit", "-m", message])?.wait()?;
+            args.extend(entries.iter().map(|e| e.name.as_str()));
+        }
+        Process::spawn(&self.git, &args)?.wait()?;
+        Ok(())
+    }
+
+    // the date only overrides the author date, which is what the log shows
+    fn commit(&self, message: &str, entries: Option<&[RevisionEntry]>, amend: bool, date: Option<&str>) -> BackendResult<()> {
+        if let Some(entries) = entries {
+            self.stage(entries)?;
+        }
+
+        let mut args = if amend { vec!["commit", "--amend", "--no-edit"] } else { vec!["commit", "-m", message] };
+        let date = date.map(|date| format!("--date={}", date));
+        if let Some(date) = &date {
+            args.push(date);
         }
+        Process::spawn(&self.git, &args)?.wait()?;
+        Ok(())
+    }
+
+    fn commit_in_editor(&self, entries: Option<&[RevisionEntry]>, verbose: bool) -> BackendResult<()> {
+        if let Some(entries) = entries {
+            self.stage(entries)?;
+        }
+
+        // git opens the configured editor, '--verbose' shows the diff below the scissors line
+        let args: &[&str] = if verbose { &["commit", "--verbose"] } else { &["commit"] };
+        Process::spawn_in_terminal(&self.git, args, &[])?.wait()?;
+        Ok(())
+    }
+
+    fn commit_empty(&self, message: &str) -> BackendResult<()> {
+        Process::spawn(&self.git, &["commit", "--allow-empty", "-m", message])?.wait()?;
         Ok(())
     }
 
     fn discard(&self, entries: &[RevisionEntry]) -> BackendResult<()> {
+        // keep the tracked changes as a patch so they can be restored by undo
+        let mut diff_args = vec!["diff", "HEAD", "--binary", "--"];
+        diff_args.extend(entries.iter().filter(|e| !matches!(e.status, FileStatus::Untracked)).map(|e| e.name.as_str()));
+        let patch = if entries.is_empty() || diff_args.len() > 4 {
+            Process::spawn(&self.git, &diff_args).and_then(Process::wait).ok().filter(|patch| !patch.is_empty())
+        } else {
+            None
+        };
+        self.set_undo_point(patch.map(UndoPoint::ApplyPatch));
+
         if entries.is_empty() {
-            Process::spawn("git", &["reset", "--hard", "HEAD"])?.wait()?;
-            Process::spawn("git", &["clean", "--force"])?.wait()?;
+            Process::spawn(&self.git, &["reset", "--hard", "HEAD"])?.wait()?;
+            Process::spawn(&self.git, &["clean", "--force"])?.wait()?;
         } else {
             let drop_entry = |f: fn(&FileStatus) -> bool, args: &[&str]| -> BackendResult<()> {
                 let filter_entries: Vec<_> = entries.iter().filter(|&e| f(&e.status)).map(|e| e.name.as_str()).collect();
 
                 if !filter_entries.is_empty() {
                     let args = [args.to_vec(), filter_entries].concat();
-                    Process::spawn("git", &args)?.wait()?;
+                    Process::spawn(&self.git, &args)?.wait()?;
                 }
 
                 Ok(())
@@ -97,38 +282,160 @@ impl Backend for Git {
         Ok(())
     }
 
-    fn diff(&self, revision: Option<&str>, entries: &[RevisionEntry]) -> BackendResult<String> {
+    fn diff(&self, revision: Option<&str>, entries: &[RevisionEntry], options: &DiffOptions) -> BackendResult<String> {
+        let parent;
+        let mut args = vec!["diff"];
+        let option_args = diff_option_args(options);
+        args.extend(option_args.iter().map(String::as_str));
         match revision {
             Some(revision) => {
-                let parent = format!("{}~", revision);
-                if entries.is_empty() {
-                    Process::spawn("git", &["diff", &parent, revision])?.wait()
-                } else {
-                    let mut args = vec!["diff", &parent, revision, "--"];
-                    for entry in entries {
-                        args.push(&entry.name);
-                    }
-
-                    Process::spawn("git", &args)?.wait()
-                }
+                parent = format!("{}~", revision);
+                args.push(&parent);
+                args.push(revision);
             }
-            None => {
-                if entries.is_empty() {
-                    Process::spawn("git", &["diff", "-z"])?.wait()
-                } else {
-                    let mut args = vec!["diff", "--"];
-                    for entry in entries {
-                        args.push(&entry.name);
-                    }
-                    Process::spawn("git", &args)?.wait()
-                }
+            None if entries.is_empty() => args.push("-z"),
+            None => (),
+        }
+        if !entries.is_empty() {
+            args.push("--");
+            for entry in entries {
+                args.push(&entry.name);
+            }
+        }
+
+        Process::spawn(&self.git, &args)?.wait()
+    }
+
+    fn diff_staged(&self, options: &DiffOptions) -> BackendResult<String> {
+        let mut args = vec!["diff", "--cached"];
+        let option_args = diff_option_args(options);
+        args.extend(option_args.iter().map(String::as_str));
+        Process::spawn(&self.git, &args)?.wait()
+    }
+
+    fn diff_merge_base(&self, branch: &str, options: &DiffOptions) -> BackendResult<String> {
+        let range = format!("{}...HEAD", branch);
+        let mut args = vec!["diff"];
+        let option_args = diff_option_args(options);
+        args.extend(option_args.iter().map(String::as_str));
+        args.push(&range);
+        Process::spawn(&self.git, &args)?.wait()
+    }
+
+    fn diff_parent(
+        &self,
+        revision: &str,
+        parent: Option<usize>,
+        entries: &[RevisionEntry],
+        options: &DiffOptions,
+    ) -> BackendResult<String> {
+        let parent = parent.map(|p| format!("{}^{}", revision, p));
+        let mut args = match &parent {
+            Some(parent) => vec!["diff", parent.as_str(), revision],
+            // combined diff against all parents of a merge
+            None => vec!["show", "--format=", "-c", revision],
+        };
+        let option_args = diff_option_args(options);
+        args.extend(option_args.iter().map(String::as_str));
+        args.push("--");
+        for entry in entries {
+            args.push(&entry.name);
+        }
+
+        Process::spawn(&self.git, &args)?.wait()
+    }
+
+    fn diff_renames(
+        &self,
+        revision: Option<&str>,
+        entries: &[RevisionEntry],
+        options: &DiffOptions,
+    ) -> BackendResult<String> {
+        // status and revision details list renames as a delete plus an add, so both paths
+        // need to be in the same diff for the rename to be detected
+        let parent;
+        let mut args = vec!["diff", "--find-renames"];
+        let option_args = diff_option_args(options);
+        args.extend(option_args.iter().map(String::as_str));
+        match revision {
+            Some(revision) => {
+                parent = format!("{}~", revision);
+                args.push(&parent);
+                args.push(revision);
             }
+            // includes staged changes so 'git mv' renames show up
+            None => args.push("HEAD"),
+        }
+        args.push("--");
+        for entry in entries {
+            args.push(&entry.name);
         }
+
+        Process::spawn(&self.git, &args)?.wait()
+    }
+
+    fn remove(&self, entries: &[RevisionEntry], keep_on_disk: bool) -> BackendResult<()> {
+        let mut args = vec!["rm"];
+        if keep_on_disk {
+            args.push("--cached");
+        }
+        args.push("--");
+        args.extend(entries.iter().map(|e| e.name.as_str()));
+        Process::spawn(&self.git, &args)?.wait()?;
+        Ok(())
+    }
+
+    fn move_file(&self, from: &str, to: &str) -> BackendResult<()> {
+        Process::spawn(&self.git, &["mv", "--", from, to])?.wait()?;
+        Ok(())
+    }
+
+    fn continue_operation(&self, operation: PendingOperation) -> BackendResult<()> {
+        if operation == PendingOperation::Bisect {
+            return Err("a bisect goes on by marking commits good or bad".into());
+        }
+
+        // resolved files still need to be staged, but not while they have conflict markers left
+        let unmerged = Process::spawn(&self.git, &["diff", "--name-only", "--diff-filter=U", "-z"])?.wait()?;
+        let unmerged: Vec<_> = unmerged.split('\0').filter(|path| !path.is_empty()).collect();
+        if let Some(path) = unmerged.iter().find(|path| count_conflict_markers(path) > 0) {
+            return Err(format!("'{}' still has conflicts, resolve them before continuing", path));
+        }
+        if !unmerged.is_empty() {
+            let args = [&["add", "--"], &unmerged[..]].concat();
+            Process::spawn(&self.git, &args)?.wait()?;
+        }
+
+        // keeps the original message instead of opening an editor
+        Process::spawn_with_env(&self.git, &[operation.name(), "--continue"], &[("GIT_EDITOR", "true")])?.wait()?;
+        Ok(())
+    }
+
+    fn abort_operation(&self, operation: PendingOperation) -> BackendResult<()> {
+        let args: &[&str] = match operation {
+            PendingOperation::Bisect => &["bisect", "reset"],
+            _ => &[operation.name(), "--abort"],
+        };
+        Process::spawn(&self.git, args)?.wait()?;
+        Ok(())
+    }
+
+    // applies the recorded resolutions again, for example after checking a conflicted file out anew
+    fn rerere(&self) -> BackendResult<()> {
+        Process::spawn(&self.git, &["rerere"])?.wait()?;
+        Ok(())
+    }
+
+    fn rerere_forget(&self, entries: &[RevisionEntry]) -> BackendResult<()> {
+        let mut args = vec!["rerere", "forget", "--"];
+        args.extend(entries.iter().map(|e| e.name.as_str()));
+        Process::spawn(&self.git, &args)?.wait()?;
+        Ok(())
     }
 
     fn resolve_taking_ours(&self, entries: &[RevisionEntry]) -> BackendResult<()> {
         if entries.is_empty() {
-            Process::spawn("git", &["checkout", "--ours", "."])?.wait()?;
+            Process::spawn(&self.git, &["checkout", "--ours", "."])?.wait()?;
         } else {
             if !entries.iter().any(|e| matches!(e.status, FileStatus::Unmerged)) {
                 return Ok(());
@@ -141,7 +448,7 @@ impl Backend for Git {
                 }
             }
 
-            Process::spawn("git", &args)?.wait()?;
+            Process::spawn(&self.git, &args)?.wait()?;
         }
 
         Ok(())
@@ -149,7 +456,7 @@ impl Backend for Git {
 
     fn resolve_taking_theirs(&self, entries: &[RevisionEntry]) -> BackendResult<()> {
         if entries.is_empty() {
-            Process::spawn("git", &["checkout", "--theirs", "."])?.wait()?;
+            Process::spawn(&self.git, &["checkout", "--theirs", "."])?.wait()?;
         } else {
             if !entries.iter().any(|e| matches!(e.status, FileStatus::Unmerged)) {
                 return Ok(());
@@ -162,149 +469,406 @@ impl Backend for Git {
                 }
             }
 
-            Process::spawn("git", &args)?.wait()?;
+            Process::spawn(&self.git, &args)?.wait()?;
         }
 
         Ok(())
     }
 
-    fn log(&self, skip: usize, len: usize) -> BackendResult<(usize, Vec<LogEntry>)> {
-        let skip_text = skip.to_string();
-        let len = len.to_string();
-        let template = "--format=format:%x00%h%x00%as%x00%aN%x00%D%x00%s";
-        let output = Process::spawn(
-            "git",
-            &[
-                "log",
-                //"--all",
-                "--decorate",
-                "--oneline",
-                "--graph",
-                "--skip",
-                &skip_text,
-                "--max-count",
-                &len,
-                template,
-            ],
-        )?
-        .wait()?;
+    fn stage_patch(&self, patch: &str) -> BackendResult<()> {
+        Process::spawn_with_input(&self.git, &["apply", "--cached", "-"], patch.as_bytes())?.wait()?;
+        Ok(())
+    }
 
-        let mut entries = Vec::new();
-        for line in output.lines() {
-            let mut splits = line.splitn(6, '\0');
+    fn has_staged_changes(&self) -> BackendResult<bool> {
+        let names = Process::spawn(&self.git, &["diff", "--cached", "--name-only"])?.wait()?;
+        Ok(!names.trim().is_empty())
+    }
 
-            let graph = splits.next().unwrap_or("").into();
-            let hash = splits.next().unwrap_or("").into();
-            let date = splits.next().unwrap_or("").into();
-            let author = splits.next().unwrap_or("").into();
-            let refs = splits.next().unwrap_or("").into();
-            let message = splits.next().unwrap_or("").into();
+    fn diff_stat(&self, diff: &str) -> BackendResult<Vec<DiffStatEntry>> {
+        // files are listed in the same order they appear in the diff
+        let output = Process::spawn_with_input(&self.git, &["apply", "--numstat", "-"], diff.as_bytes())?.wait()?;
+        let entries = output
+            .lines()
+            .filter_map(|l| {
+                let mut splits = l.splitn(3, '\t');
+                let insertions = splits.next()?.parse().unwrap_or(0);
+                let deletions = splits.next()?.parse().unwrap_or(0);
+                let path = splits.next()?.into();
+                Some(DiffStatEntry { path, insertions, deletions })
+            })
+            .collect();
+        Ok(entries)
+    }
 
-            entries.push(LogEntry { graph, hash, date, author, refs, message });
+    fn apply_patch(&self, path: &str) -> BackendResult<()> {
+        let patch = fs::read(path).map_err(|e| format!("could not read patch '{}': {}", path, e))?;
+        // mbox patches (from 'git format-patch') start with a 'From <hash>' line
+        if patch.starts_with(b"From ") {
+            Process::spawn(&self.git, &["am", path])?.wait()?;
+        } else {
+            Process::spawn(&self.git, &["apply", path])?.wait()?;
         }
+        Ok(())
+    }
+
+    fn log(
+        &self,
+        skip: usize,
+        len: usize,
+        all: bool,
+        no_merges: bool,
+        first_parent: bool,
+    ) -> BackendResult<(usize, Vec<LogEntry>)> {
+        let skip_text = skip.to_string();
+        let len = len.to_string();
+        let mut args =
+            vec!["log", "--decorate", "--oneline", "--graph", "--skip", &skip_text, "--max-count", &len, LOG_FORMAT];
+        if all {
+            args.push("--all");
+            args.push("--source");
+        }
+        if no_merges {
+            args.push("--no-merges");
+        }
+        // follows only the mainline, so merges show up as single commits
+        if first_parent {
+            args.push("--first-parent");
+        }
+        let output = match Process::spawn(&self.git, &args)?.wait() {
+            Ok(output) => output,
+            // an empty list is more useful than the error in a new repository
+            Err(error) if error.contains("does not have any commits yet") => String::new(),
+            Err(error) => return Err(error),
+        };
+        Ok((skip, parse_log(&output, all)))
+    }
 
-        Ok((skip, entries))
+    fn log_pickaxe(&self, skip: usize, len: usize, term: &str, regex: bool) -> BackendResult<(usize, Vec<LogEntry>)> {
+        // the graph would only connect the matching commits so it is left out
+        let search = if regex { format!("-G{}", term) } else { format!("-S{}", term) };
+        let skip_text = skip.to_string();
+        let len = len.to_string();
+        let args = ["log", "--decorate", "--oneline", "--skip", &skip_text, "--max-count", &len, LOG_FORMAT, &search];
+        let output = Process::spawn(&self.git, &args)?.wait()?;
+        Ok((skip, parse_log(&output, false)))
     }
 
     fn checkout(&self, revision: &str) -> BackendResult<()> {
-        Process::spawn("git", &["checkout", revision])?.wait()?;
+        Process::spawn(&self.git, &["checkout", revision])?.wait()?;
         Ok(())
     }
 
-    fn merge(&self, revision: &str) -> BackendResult<()> {
-        Process::spawn("git", &["merge", "--no-ff", revision])?.wait()?;
-        Ok(())
+    fn merge(&self, revision: &str, strategy: MergeStrategy) -> BackendResult<String> {
+        let strategy = match strategy {
+            MergeStrategy::NoFastForward => "--no-ff",
+            MergeStrategy::FastForward => "--ff",
+            MergeStrategy::FastForwardOnly => "--ff-only",
+        };
+        self.record_head_undo_point();
+        Process::spawn(&self.git, &["merge", strategy, revision])?.wait_with_stderr()
     }
 
-    fn fetch(&self) -> BackendResult<()> {
-        Process::spawn("git", &["fetch", "--all", "--prune"])?.wait()?;
-        Ok(())
+    fn fetch(&self) -> BackendResult<(String, Vec<String>)> {
+        let output = Process::spawn(&self.git, &["fetch", "--all", "--prune"])?.wait_with_stderr()?;
+        let pruned = pruned_branches(&output);
+        Ok((output, pruned))
     }
 
-    fn pull(&self) -> BackendResult<()> {
-        Process::spawn("git", &["pull", "--all"])?.wait()?;
-        Ok(())
+    fn pull(&self) -> BackendResult<String> {
+        Process::spawn(&self.git, &["pull", "--all"])?.wait_with_stderr()
     }
 
-    fn push(&self) -> BackendResult<()> {
-        Process::spawn("git", &["push"])?.wait()?;
-        Ok(())
+    fn push(&self) -> BackendResult<String> {
+        Process::spawn(&self.git, &["push"])?.wait_with_stderr()
+    }
+
+    fn remote(&self) -> BackendResult<String> {
+        // with several remotes, the first one listed
+        let remotes = Process::spawn(&self.git, &["remote"])?.wait()?;
+        Ok(remotes.lines().next().unwrap_or("").trim().to_owned())
+    }
+
+    // pushed to any remote branch, not only the upstream
+    fn head_is_pushed(&self) -> BackendResult<bool> {
+        let branches = Process::spawn(&self.git, &["branch", "--remotes", "--contains", "HEAD"])?.wait()?;
+        Ok(!branches.trim().is_empty())
     }
 
-    fn push_gerrit(&self) -> BackendResult<()> {
+    fn current_branch(&self) -> BackendResult<String> {
+        let branch = Process::spawn(&self.git, &["symbolic-ref", "--short", "HEAD"])?.wait()?.trim().to_owned();
+        Ok(branch)
+    }
+
+    fn head_is_detached(&self) -> BackendResult<bool> {
+        // HEAD is not a symbolic ref when it points straight at a commit
+        let symbolic = Process::spawn(&self.git, &["symbolic-ref", "--quiet", "HEAD"])?.wait_answer()?;
+        Ok(!symbolic)
+    }
+
+    fn push_preview(&self) -> BackendResult<String> {
+        // push reports what it would update on stderr
+        let dry_run = Process::spawn(&self.git, &["push", "--dry-run"])?.wait_with_stderr()?;
+
+        let mut output = String::new();
+        if let Ok(commits) =
+            Process::spawn(&self.git, &["log", "--oneline", "--decorate", "@{upstream}..HEAD"]).and_then(Process::wait)
+        {
+            if commits.is_empty() {
+                output.push_str("no commits to push\n");
+            } else {
+                output.push_str("commits to push:\n");
+                output.push_str(&commits);
+            }
+            output.push('\n');
+        }
+        output.push_str(&dry_run);
+        Ok(output)
+    }
+
+    fn push_gerrit(&self) -> BackendResult<String> {
         let remote = self.remote()?;
         let current_branch = self.current_branch()?;
         let mut branch_info = "HEAD:refs/for/".to_owned();
         branch_info.push_str(&current_branch);
-        Process::spawn("git", &["push", &remote, &branch_info])?.wait()?;
+        Process::spawn(&self.git, &["push", &remote, &branch_info])?.wait_with_stderr()
+    }
+
+    fn rebase_todo(&self, base: &str) -> BackendResult<Vec<RebaseEntry>> {
+        let path = self.git_path("verco-rebase-todo");
+
+        // the editor copies the todo list out then fails so git aborts the rebase without touching anything
+        let editor = format!("f() {{ cp \"$1\" {}; exit 1; }}; f", shell_quote(&path));
+        let _ = fs::remove_file(&path);
+        let result = Process::spawn_with_env(&self.git, &["rebase", "-i", base], &[("GIT_SEQUENCE_EDITOR", &editor)])?.wait();
+
+        let todo = match fs::read_to_string(&path) {
+            Ok(todo) => todo,
+            // git failed before reaching the editor (local changes for example)
+            Err(error) => match result {
+                Err(error) => return Err(error),
+                Ok(_) => return Err(format!("could not read the rebase todo list: {}", error)),
+            },
+        };
+        let _ = fs::remove_file(&path);
+
+        Ok(parse_rebase_todo(&todo))
+    }
+
+    fn rebase_interactive(&self, base: &str, entries: &[RebaseEntry], in_terminal: bool) -> BackendResult<()> {
+        self.record_head_undo_point();
+        self.run_rebase(base, entries, &[], in_terminal)
+    }
+
+    fn reword(&self, revision: &str, message: &str) -> BackendResult<()> {
+        let head = Process::spawn(&self.git, &["rev-parse", "HEAD"])?.wait()?;
+        let target = Process::spawn(&self.git, &["rev-parse", revision])?.wait()?;
+        let target = target.trim();
+
+        self.record_head_undo_point();
+
+        if head.trim() == target {
+            Process::spawn(&self.git, &["commit", "--amend", "--only", "-m", message])?.wait()?;
+            return Ok(());
+        }
+
+        // 'rebase -i' would flatten any merge on the way
+        let range = format!("{}..HEAD", target);
+        if !Process::spawn(&self.git, &["rev-list", "--merges", &range])?.wait()?.trim().is_empty() {
+            return Err("can not reword a commit that is behind a merge".into());
+        }
+
+        // the root commit has no parent to rebase onto
+        let parent = format!("{}^", target);
+        let has_parent = Process::spawn(&self.git, &["rev-parse", "--verify", "--quiet", &parent])?.wait_answer()?;
+        let base = if has_parent { parent } else { "--root".into() };
+        let mut entries = self.rebase_todo(&base)?;
+        for entry in &mut entries {
+            if entry.action == RebaseAction::Pick && !entry.hash.is_empty() && target.starts_with(&entry.hash) {
+                entry.action = RebaseAction::Reword;
+            }
+        }
+
+        let path = self.git_path("verco-reword-message");
+        fs::write(&path, message).map_err(|e| format!("could not write the commit message: {}", e))?;
+
+        // git appends the path of the message to edit so this replaces it with ours
+        let editor = format!("cp {}", shell_quote(&path));
+        let result = self.run_rebase(&base, &entries, &[("GIT_EDITOR", &editor)], false);
+
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    fn gc(&self) -> BackendResult<()> {
+        // git only reports progress when it owns a terminal
+        Process::spawn_in_terminal(&self.git, &["gc"], &[])?.wait()?;
         Ok(())
     }
 
-    fn stash(&self, message: &str, entries: &[RevisionEntry]) -> BackendResult<()> {
-        if entries.is_empty() {
-            Process::spawn("git", &["stash", "save", message])?.wait()?;
-        } else {
-            let mut args =
-                if message.is_empty() { vec!["stash", "push", "--"] } else { vec!["stash", "push", "-m", message, "--"] };
+    fn stash(&self, message: &str, entries: &[RevisionEntry], include_untracked: bool) -> BackendResult<()> {
+        let mut args = vec!["stash", "push"];
+        if include_untracked {
+            args.push("--include-untracked");
+        }
+        if !message.is_empty() {
+            args.extend(["-m", message]);
+        }
+        if !entries.is_empty() {
+            args.push("--");
             for entry in entries {
                 args.push(&entry.name);
             }
-
-            Process::spawn("git", &args)?.wait()?;
         }
 
+        Process::spawn(&self.git, &args)?.wait()?;
         Ok(())
     }
 
     fn stash_list(&self) -> BackendResult<Vec<StashEntry>> {
-        let entries = Process::spawn("git", &["stash", "list"])?
+        let entries = Process::spawn(&self.git, &["stash", "list", "--format=%gd%x00%ct%x00%gs"])?
             .wait()?
             .lines()
             .map(|l| {
-                let mut splits = l.splitn(3, ':');
+                let mut splits = l.splitn(3, '\0');
                 let id = splits.next().unwrap().trim_matches(|c: char| !c.is_numeric()).parse::<usize>().unwrap();
+                let timestamp = splits.next().unwrap_or("").parse().unwrap_or(0);
+
+                // 'On <branch>: <message>' or 'WIP on <branch>: <hash> <message>'
+                let mut splits = splits.next().unwrap_or("").splitn(2, ':');
                 let branch = splits.next().unwrap().split(' ').next_back().unwrap().trim().to_owned();
                 let message = splits.next().unwrap_or("").trim().to_owned();
 
-                StashEntry { id, branch, message }
+                StashEntry { id, branch, message, timestamp }
             })
             .collect();
         Ok(entries)
     }
 
     fn stash_pop(&self, id: usize) -> BackendResult<()> {
-        Process::spawn("git", &["stash", "pop", id.to_string().as_str()])?.wait()?;
+        Process::spawn(&self.git, &["stash", "pop", id.to_string().as_str()])?.wait()?;
         Ok(())
     }
 
     fn stash_show(&self, id: usize) -> BackendResult<String> {
-        Process::spawn("git", &["stash", "show", id.to_string().as_str()])?.wait()
+        Process::spawn(&self.git, &["stash", "show", "--include-untracked", id.to_string().as_str()])?.wait()
     }
 
-    fn stash_diff(&self, id: usize) -> BackendResult<String> {
-        Process::spawn("git", &["stash", "show", "-p", id.to_string().as_str()])?.wait()
+    fn stash_files(&self, id: usize) -> BackendResult<Vec<RevisionEntry>> {
+        let id = id.to_string();
+        let changes = Process::spawn(
+            &self.git,
+            &["stash", "show", "--include-untracked", "--name-status", "--no-renames", "-z", id.as_str()],
+        )?
+        .wait()?;
+        let mut splits = changes.split('\0');
+
+        let mut entries = Vec::new();
+        loop {
+            let status = match splits.next() {
+                Some(status) if !status.is_empty() => parse_file_status(status),
+                _ => break,
+            };
+            let name = match splits.next() {
+                Some(name) => name.into(),
+                None => break,
+            };
+
+            entries.push(RevisionEntry::new(name, status));
+        }
+
+        Ok(entries)
+    }
+
+    // what changed from the stash to the working tree
+    fn diff_stash_working_tree(&self, id: usize, entries: &[RevisionEntry], options: &DiffOptions) -> BackendResult<String> {
+        let stash = format!("stash@{{{}}}", id);
+        let mut args = vec!["diff"];
+        let option_args = diff_option_args(options);
+        args.extend(option_args.iter().map(String::as_str));
+        args.push(&stash);
+        if !entries.is_empty() {
+            args.push("--");
+            args.extend(entries.iter().map(|e| e.name.as_str()));
+        }
+        Process::spawn(&self.git, &args)?.wait()
+    }
+
+    fn stash_diff(&self, id: usize, entries: &[RevisionEntry], options: &DiffOptions) -> BackendResult<String> {
+        let id = id.to_string();
+        let stash = format!("stash@{{{}}}", id);
+        let parent = format!("{}^1", stash);
+
+        let option_args = diff_option_args(options);
+        if entries.is_empty() {
+            let mut args = vec!["stash", "show", "-p", "--include-untracked"];
+            args.extend(option_args.iter().map(String::as_str));
+            args.push(&id);
+            return Process::spawn(&self.git, &args)?.wait();
+        }
+
+        let mut args = vec!["diff"];
+        args.extend(option_args.iter().map(String::as_str));
+        args.extend([parent.as_str(), stash.as_str(), "--"]);
+        args.extend(entries.iter().map(|e| e.name.as_str()));
+        let mut output = Process::spawn(&self.git, &args)?.wait()?;
+
+        // untracked files stashed with '--include-untracked' live in the third parent, which may not exist
+        let untracked = format!("{}^3", stash);
+        let mut args = vec!["show", "--format="];
+        args.extend(option_args.iter().map(String::as_str));
+        args.extend([untracked.as_str(), "--"]);
+        args.extend(entries.iter().map(|e| e.name.as_str()));
+        if let Ok(untracked) = Process::spawn(&self.git, &args).and_then(Process::wait) {
+            output.push_str(&untracked);
+        }
+
+        Ok(output)
     }
 
     fn stash_drop(&self, id: usize) -> BackendResult<()> {
-        Process::spawn("git", &["stash", "drop", id.to_string().as_str()])?.wait()?;
+        Process::spawn(&self.git, &["stash", "drop", id.to_string().as_str()])?.wait()?;
         Ok(())
     }
 
     fn reset(&self, revision: &str) -> BackendResult<()> {
-        let output = Process::spawn("git", &["status", "--null"])?.wait()?;
+        let output = Process::spawn(&self.git, &["status", "--null"])?.wait()?;
         if !output.is_empty() {
             return Err("There are local changes! Please stash / commit / discard first.".to_owned());
         }
         let revision = if revision == "" { self.remote_branch()? } else { revision.to_owned() };
-        Process::spawn("git", &["reset", "--hard", &revision])?.wait()?;
+        self.record_head_undo_point();
+        Process::spawn(&self.git, &["reset", "--hard", &revision])?.wait()?;
         Ok(())
     }
 
+    // commits reachable from 'to' but not from 'from', which is the remote branch when empty like in 'reset'
+    fn log_range(&self, from: &str, to: &str) -> BackendResult<Vec<LogEntry>> {
+        let from = if from.is_empty() { self.remote_branch()? } else { from.to_owned() };
+        let range = format!("{}..{}", from, to);
+        let output = Process::spawn(&self.git, &["log", "--decorate", "--oneline", LOG_FORMAT, &range])?.wait()?;
+        Ok(parse_log(&output, false))
+    }
+
     fn revision_details(&self, revision: &str) -> BackendResult<RevisionInfo> {
-        let message = Process::spawn("git", &["show", "-s", "--format=%B", "--no-renames", revision])?;
-        let changes = Process::spawn("git", &["diff-tree", "--no-commit-id", "--name-status", "-r", "-z", revision])?;
+        let message = Process::spawn(&self.git, &["show", "-s", "--format=%P%x00%B", "--no-renames", revision])?;
+        let changes = Process::spawn(
+            &self.git,
+            &[
+                "diff-tree",
+                "--no-commit-id",
+                "--name-status",
+                "-r",
+                "-z",
+                "--diff-merges=first-parent", // otherwise merges list no files
+                revision,
+            ],
+        )?;
 
-        let message = message.wait()?.trim().into();
+        let message = message.wait()?;
+        let (parents, message) = message.split_once('\0').unwrap_or(("", &message));
+        let parent_count = parents.split_whitespace().count();
+        let message = message.trim().into();
 
         let changes = changes.wait()?;
         let mut splits = changes.split('\0');
@@ -323,50 +887,129 @@ impl Backend for Git {
             entries.push(RevisionEntry::new(name, status));
         }
 
-        Ok(RevisionInfo { message, entries })
+        Ok(RevisionInfo { message, entries, parent_count })
     }
 
-    fn branches(&self) -> BackendResult<Vec<BranchEntry>> {
-        let entries = Process::spawn(
-            "git",
-            &[
-                "branch",
-                "--list",
-                //"--all",
-                "--format=%(refname:short)%20%(HEAD)", // %20 is space, %(HEAD) is *
-            ],
-        )?
-        .wait()?
-        .lines()
-        .map(|l| {
-            let mut splits = l.splitn(2, ' ');
-            let name = splits.next().unwrap_or("").into();
-            let checked_out = splits.next().unwrap_or("") == "*";
-            BranchEntry { name, checked_out }
-        })
-        .collect();
+    fn notes_show(&self, revision: &str) -> BackendResult<String> {
+        // git fails when there is no note which is not worth reporting
+        match Process::spawn(&self.git, &["notes", "show", revision])?.wait() {
+            Ok(note) => Ok(note.trim_end().into()),
+            Err(_) => Ok(String::new()),
+        }
+    }
+
+    fn notes_add(&self, revision: &str, note: &str) -> BackendResult<()> {
+        // forced so an existing note is replaced
+        Process::spawn(&self.git, &["notes", "add", "--force", "--message", note, revision])?.wait()?;
+        Ok(())
+    }
+
+    fn restore_file_from(&self, revision: &str, path: &str) -> BackendResult<()> {
+        let source = format!("--source={}", revision);
+        Process::spawn(&self.git, &["restore", &source, "--", path])?.wait()?;
+        Ok(())
+    }
+
+    fn show_file(&self, revision: &str, path: &str) -> BackendResult<String> {
+        let object = format!("{}:{}", revision, path);
+        Process::spawn(&self.git, &["show", &object])?.wait()
+    }
+
+    // the file as it is on disk, conflict markers included
+    fn working_file(&self, path: &str) -> BackendResult<String> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(String::from_utf8_lossy(&bytes).into()),
+            Err(error) => Err(format!("could not read '{}': {}", path, error)),
+        }
+    }
+
+    // an empty revision blames the working tree file
+    fn blame(&self, revision: &str, path: &str) -> BackendResult<Vec<BlameEntry>> {
+        let mut args = vec!["blame", "--line-porcelain"];
+        if !revision.is_empty() {
+            args.push(revision);
+        }
+        args.push("--");
+        args.push(path);
+        let output = Process::spawn(&self.git, &args)?.wait()?;
+
+        Ok(parse_blame(&output))
+    }
+
+    fn branches(&self, include_remotes: bool) -> BackendResult<Vec<BranchEntry>> {
+        let mut args = vec!["branch", "--list"];
+        if include_remotes {
+            args.push("--all");
+        }
+        args.push("--format=%(refname)%00%(HEAD)%00%(symref)%00%(committerdate:unix)%00%(upstream:short)"); // %00 is null, %(HEAD) is *
+
+        let entries = Process::spawn(&self.git, &args)?
+            .wait()?
+            .lines()
+            .filter_map(|l| {
+                let mut splits = l.splitn(5, '\0');
+                let refname = splits.next().unwrap_or("");
+                let checked_out = splits.next().unwrap_or("") == "*";
+                let symref = splits.next().unwrap_or("");
+                let timestamp = splits.next().unwrap_or("").parse().unwrap_or(0);
+                let upstream = splits.next().filter(|u| !u.is_empty()).map(String::from);
+                if !symref.is_empty() {
+                    return None; // skip 'origin/HEAD' like entries
+                }
+
+                let (name, remote) = if let Some(name) = refname.strip_prefix("refs/heads/") {
+                    (name, false)
+                } else if let Some(name) = refname.strip_prefix("refs/remotes/") {
+                    (name, true)
+                } else {
+                    (refname, false)
+                };
+                Some(BranchEntry { name: name.into(), checked_out, remote, timestamp, upstream })
+            })
+            .collect();
         Ok(entries)
     }
 
+    fn checkout_tracking(&self, remote_branch: &str) -> BackendResult<()> {
+        // the local branch name is left for git to derive from the remote branch
+        Process::spawn(&self.git, &["checkout", "--track", remote_branch])?.wait()?;
+        Ok(())
+    }
+
     fn new_branch(&self, name: &str) -> BackendResult<()> {
-        //let remote = Process::spawn("git", &["remote"])?.wait()?;
-        //Process::spawn("git", &["branch", name])?.wait()?;
-        //Process::spawn("git", &["checkout", name])?.wait()?;
-        //Process::spawn("git", &["push", "--set-upstream", remote.trim(), name])?.wait()?;
-        Process::spawn("git", &["checkout", "-b", name])?.wait()?; // only local branch
+        //let remote = Process::spawn(&self.git, &["remote"])?.wait()?;
+        //Process::spawn(&self.git, &["branch", name])?.wait()?;
+        //Process::spawn(&self.git, &["checkout", name])?.wait()?;
+        //Process::spawn(&self.git, &["push", "--set-upstream", remote.trim(), name])?.wait()?;
+        Process::spawn(&self.git, &["checkout", "-b", name])?.wait()?; // only local branch
         Ok(())
     }
 
     fn delete_branch(&self, name: &str, force: bool) -> BackendResult<()> {
-        //let remote = Process::spawn("git", &["remote"])?.wait()?;
+        //let remote = Process::spawn(&self.git, &["remote"])?.wait()?;
         let delete_option = if force { "-D" } else { "--delete" };
-        Process::spawn("git", &["branch", delete_option, name])?.wait()?;
-        //Process::spawn("git", &["push", "--delete", remote.trim(), name])?.wait()?;
+        Process::spawn(&self.git, &["branch", delete_option, name])?.wait()?;
+        //Process::spawn(&self.git, &["push", "--delete", remote.trim(), name])?.wait()?;
+        Ok(())
+    }
+
+    fn delete_remote_branch(&self, remote_branch: &str) -> BackendResult<()> {
+        let (remote, branch) = match remote_branch.split_once('/') {
+            Some((remote, branch)) => (remote, branch),
+            None => return Err(format!("'{}' is not a remote branch", remote_branch)),
+        };
+        Process::spawn(&self.git, &["push", "--delete", remote, branch])?.wait()?;
+        Ok(())
+    }
+
+    fn set_upstream(&self, name: &str, upstream: &str) -> BackendResult<()> {
+        let upstream = format!("--set-upstream-to={}", upstream);
+        Process::spawn(&self.git, &["branch", &upstream, name])?.wait()?;
         Ok(())
     }
 
     fn tags(&self) -> BackendResult<Vec<TagEntry>> {
-        let entries = Process::spawn("git", &["tag", "--list", "--format=%(refname:short)"])?
+        let entries = Process::spawn(&self.git, &["tag", "--list", "--format=%(refname:short)"])?
             .wait()?
             .lines()
             .map(|l| TagEntry { name: l.into() })
@@ -374,21 +1017,275 @@ impl Backend for Git {
         Ok(entries)
     }
 
-    fn new_tag(&self, name: &str) -> BackendResult<()> {
-        //let remote = Process::spawn("git", &["remote"])?.wait()?;
-        Process::spawn("git", &["tag", "--force", name])?.wait()?;
-        //Process::spawn("git", &["push", remote.trim(), name])?.wait()?;
+    fn new_tag(&self, name: &str, signed_message: Option<&str>) -> BackendResult<()> {
+        //let remote = Process::spawn(&self.git, &["remote"])?.wait()?;
+        if let Some(message) = signed_message {
+            // no '--force' so an existing signed tag has to be deleted before it is replaced
+            Process::spawn(&self.git, &["tag", "--sign", "--message", message, name])?.wait().map_err(|e| {
+                if e.contains("already exists") {
+                    e
+                } else {
+                    format!("could not sign the tag, check your gpg setup (user.signingKey, gpg.program)\n\n{}", e)
+                }
+            })?;
+        } else {
+            Process::spawn(&self.git, &["tag", "--force", name])?.wait()?;
+        }
+        //Process::spawn(&self.git, &["push", remote.trim(), name])?.wait()?;
         Ok(())
     }
 
+    fn verify_tag(&self, name: &str) -> BackendResult<String> {
+        // gpg writes its verdict to stderr
+        Process::spawn(&self.git, &["tag", "--verify", name])?.wait_with_stderr()
+    }
+
     fn delete_tag(&self, name: &str) -> BackendResult<()> {
-        //let remote = Process::spawn("git", &["remote"])?.wait()?;
-        Process::spawn("git", &["tag", "--delete", name])?.wait()?;
-        //Process::spawn("git", &["push", "--delete", remote.trim(), name])?.wait()?;
+        //let remote = Process::spawn(&self.git, &["remote"])?.wait()?;
+        Process::spawn(&self.git, &["tag", "--delete", name])?.wait()?;
+        //Process::spawn(&self.git, &["push", "--delete", remote.trim(), name])?.wait()?;
+        Ok(())
+    }
+
+    fn undo_description(&self) -> Option<String> {
+        match &*self.undo_point.lock().unwrap() {
+            Some(UndoPoint::ResetTo(head)) => Some(format!("reset back to {}", &head[..head.len().min(7)])),
+            Some(UndoPoint::ApplyPatch(_)) => {
+                Some("restore discarded changes (discarded untracked files can not be recovered)".into())
+            }
+            None => None,
+        }
+    }
+
+    fn undo(&self) -> BackendResult<()> {
+        let undo_point = self.undo_point.lock().unwrap().take();
+        match undo_point {
+            Some(UndoPoint::ResetTo(head)) => {
+                Process::spawn(&self.git, &["reset", "--hard", &head])?.wait()?;
+            }
+            Some(UndoPoint::ApplyPatch(patch)) => {
+                Process::spawn_with_input(&self.git, &["apply", "--binary"], patch.as_bytes())?.wait()?;
+            }
+            None => return Err("nothing to undo".into()),
+        }
         Ok(())
     }
 }
 
+// 'blame --line-porcelain' output, where every line comes as a header with the commit hash,
+// then its attributes and finally the line content prefixed with a tab
+fn parse_blame(output: &str) -> Vec<BlameEntry> {
+    let mut entries = Vec::new();
+    let mut is_header = true;
+    let mut hash = "";
+    let mut author = "";
+    for line in output.lines() {
+        if is_header {
+            is_header = false;
+            hash = line.get(..8).unwrap_or(line);
+        } else if let Some(line) = line.strip_prefix('\t') {
+            is_header = true;
+            entries.push(BlameEntry { hash: hash.into(), author: author.into(), line: line.into() });
+        } else if let Some(value) = line.strip_prefix("author ") {
+            author = value;
+        }
+    }
+    entries
+}
+
+// the lines of a 'rebase -i' todo list, without the comments git adds for the editor
+fn parse_rebase_todo(todo: &str) -> Vec<RebaseEntry> {
+    let mut entries = Vec::new();
+    for line in todo.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let mut splits = line.splitn(3, ' ');
+        let action = match splits.next() {
+            Some("p" | "pick") => RebaseAction::Pick,
+            Some("r" | "reword") => RebaseAction::Reword,
+            Some("s" | "squash") => RebaseAction::Squash,
+            Some("f" | "fixup") => RebaseAction::Fixup,
+            Some("d" | "drop") => RebaseAction::Drop,
+            // what git writes when there is nothing to rebase
+            Some("noop") => continue,
+            Some(command) => RebaseAction::Other(command.into()),
+            None => continue,
+        };
+        let hash = splits.next().unwrap_or("").into();
+        let message = splits.next().unwrap_or("").into();
+        entries.push(RebaseEntry { action, hash, message });
+    }
+    entries
+}
+
+// remote branches that 'fetch --prune' reported as ' - [deleted]  (none)  -> origin/branch'
+fn pruned_branches(fetch_output: &str) -> Vec<String> {
+    fetch_output
+        .lines()
+        .filter(|l| l.trim_start().starts_with("- [deleted]"))
+        .filter_map(|l| l.rsplit_once("-> "))
+        .map(|(_, branch)| branch.trim().into())
+        .collect()
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn diff_option_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = vec![format!("--unified={}", options.context_lines)];
+    if options.ignore_whitespace {
+        args.push("--ignore-all-space".into());
+    }
+    args
+}
+
+// the '# branch.*' headers of 'status --porcelain=v2'
+#[derive(Default)]
+struct BranchStatus {
+    oid: String,
+    head: String,
+    upstream: Option<String>,
+    ahead_behind: Option<(String, String)>,
+}
+impl BranchStatus {
+    fn parse(&mut self, header: &str) {
+        let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+        match key {
+            "branch.oid" => self.oid = value.into(),
+            "branch.head" => self.head = value.into(),
+            "branch.upstream" => self.upstream = Some(value.into()),
+            "branch.ab" => {
+                let (ahead, behind) = value.split_once(' ').unwrap_or((value, ""));
+                let ahead = ahead.trim_start_matches('+');
+                let behind = behind.trim_start_matches('-');
+                self.ahead_behind = Some((ahead.into(), behind.into()));
+            }
+            _ => (),
+        }
+    }
+
+    // a readable line like 'on branch main tracking origin/main (ahead 1, behind 2)'
+    fn header(&self) -> String {
+        if self.head == "(detached)" {
+            return "HEAD (no branch)".into();
+        }
+        if self.oid == "(initial)" {
+            return format!("No commits yet on {}", self.head);
+        }
+
+        let upstream = match &self.upstream {
+            Some(upstream) => upstream,
+            None => return format!("on branch {}", self.head),
+        };
+        let tracking = match &self.ahead_behind {
+            None => "gone".into(),
+            Some((ahead, behind)) => {
+                let mut tracking = Vec::new();
+                if ahead != "0" {
+                    tracking.push(format!("ahead {}", ahead));
+                }
+                if behind != "0" {
+                    tracking.push(format!("behind {}", behind));
+                }
+                if tracking.is_empty() {
+                    "up to date".into()
+                } else {
+                    tracking.join(", ")
+                }
+            }
+        };
+        format!("on branch {} tracking {} ({})", self.head, upstream, tracking)
+    }
+}
+
+// an entry line of 'status --porcelain=v2', the path is always the last field
+fn parse_status_record(record: &str) -> Option<RevisionEntry> {
+    let (kind, rest) = record.split_once(' ')?;
+    let (status, name) = match kind {
+        "1" => (FileStatus::Clean, rest.splitn(8, ' ').nth(7)?),
+        "u" => (FileStatus::Unmerged, rest.splitn(10, ' ').nth(9)?),
+        "?" => (FileStatus::Untracked, rest),
+        _ => return None,
+    };
+
+    let status = match status {
+        // the index status comes first, '.' means unchanged there
+        FileStatus::Clean => parse_file_status(rest.get(..2)?.trim_start_matches('.')),
+        status => status,
+    };
+    let mut entry = RevisionEntry::new(name.into(), status);
+    if let FileStatus::Unmerged = entry.status {
+        entry.conflict_count = count_conflict_markers(&entry.name);
+    }
+    Some(entry)
+}
+
+// the body may span several lines so it is terminated by %x01
+const LOG_FORMAT: &str = "--format=format:%x00%h%x00%H%x00%as%x00%aN%x00%aE%x00%D%x00%S%x00%s%x00%b%x01";
+
+// parses 'git log' output in LOG_FORMAT, where '%S' is only meaningful with '--source'
+fn parse_log(output: &str, with_source: bool) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = Vec::new();
+    let mut in_body = false;
+    for line in output.lines() {
+        if in_body {
+            if let Some(entry) = entries.last_mut() {
+                match line.split_once('\x01') {
+                    Some((tail, _)) => {
+                        entry.body.push_str(tail);
+                        entry.body.truncate(entry.body.trim_end().len());
+                        in_body = false;
+                    }
+                    None => {
+                        entry.body.push_str(line);
+                        entry.body.push('\n');
+                    }
+                }
+            }
+            continue;
+        }
+
+        let mut splits = line.splitn(10, '\0');
+
+        let graph = splits.next().unwrap_or("").into();
+        let hash = splits.next().unwrap_or("").into();
+        let full_hash = splits.next().unwrap_or("").into();
+        let date = splits.next().unwrap_or("").into();
+        let author = splits.next().unwrap_or("").into();
+        let email = splits.next().unwrap_or("").into();
+        let refs = splits.next().unwrap_or("").into();
+        // '%S' falls back to 'HEAD' without '--source'
+        let source = splits.next().filter(|_| with_source).unwrap_or("");
+        let source = ["refs/heads/", "refs/remotes/", "refs/tags/"]
+            .iter()
+            .find_map(|prefix| source.strip_prefix(prefix))
+            .unwrap_or(source)
+            .into();
+        let message = splits.next().unwrap_or("").into();
+        let body = match splits.next() {
+            Some(body) => match body.split_once('\x01') {
+                Some((body, _)) => body.trim_end().into(),
+                None => {
+                    in_body = true;
+                    format!("{}\n", body)
+                }
+            },
+            None => String::new(),
+        };
+
+        entries.push(LogEntry { graph, hash, full_hash, date, author, email, refs, source, message, body });
+    }
+
+    entries
+}
+
+// paths are relative to the repository root, which is the current dir
+fn count_conflict_markers(path: &str) -> usize {
+    match fs::read(path) {
+        Ok(bytes) => bytes.split(|&b| b == b'\n').filter(|line| line.starts_with(b"<<<<<<<")).count(),
+        Err(_) => 0,
+    }
+}
+
 fn parse_file_status(s: &str) -> FileStatus {
     match s.chars().next() {
         Some('M') => FileStatus::Modified,
@@ -402,3 +1299,148 @@ fn parse_file_status(s: &str) -> FileStatus {
         _ => FileStatus::Unknown(s.into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_status_records() {
+        let records = [
+            (
+                "1 M. N... 100644 100644 100644 61780798228d17af2d34fce4cfbdf35556832472 63d7d705891b78daf172fb11da13d666fd5062cd b",
+                "b",
+                FileStatus::Modified,
+            ),
+            (
+                "1 .M N... 100644 100644 100644 f2ad6c76f0115a6ba5b00456a849810e7ec0af20 f2ad6c76f0115a6ba5b00456a849810e7ec0af20 with space",
+                "with space",
+                FileStatus::Modified,
+            ),
+            (
+                "1 A. N... 000000 100644 100644 0000000000000000000000000000000000000000 e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 added",
+                "added",
+                FileStatus::Added,
+            ),
+            (
+                "1 .D N... 100644 100644 000000 e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 gone",
+                "gone",
+                FileStatus::Deleted,
+            ),
+            (
+                "u UU N... 100644 100644 100644 100644 78981922613b2afb6025042ff6bd878ac1994e85 975fbec8256d3e8a3797e7a3611380f27c49f4ac 587be6b4c3f93f93c489c0111bba5596147a26cb a",
+                "a",
+                FileStatus::Unmerged,
+            ),
+            ("? new", "new", FileStatus::Untracked),
+        ];
+        for (record, name, status) in records.iter() {
+            let entry = parse_status_record(record).unwrap();
+            assert_eq!(entry.name, *name, "{}", record);
+            assert_eq!(entry.status, *status, "{}", record);
+        }
+
+        assert!(parse_status_record("! ignored").is_none());
+        assert!(parse_status_record("1 .M N...").is_none());
+    }
+
+    #[test]
+    fn formats_branch_headers() {
+        let cases: [(&[&str], &str); 6] = [
+            (&["branch.oid bb25dca72feb83e8c6ac2dc6548fa127c46bc594", "branch.head main"], "on branch main"),
+            (
+                &["branch.oid bb25dca", "branch.head main", "branch.upstream origin/main", "branch.ab +0 -0"],
+                "on branch main tracking origin/main (up to date)",
+            ),
+            (
+                &["branch.oid bb25dca", "branch.head main", "branch.upstream origin/main", "branch.ab +1 -2"],
+                "on branch main tracking origin/main (ahead 1, behind 2)",
+            ),
+            (
+                &["branch.oid bb25dca", "branch.head topic", "branch.upstream origin/topic"],
+                "on branch topic tracking origin/topic (gone)",
+            ),
+            (&["branch.oid bb25dca", "branch.head (detached)"], "HEAD (no branch)"),
+            (&["branch.oid (initial)", "branch.head main"], "No commits yet on main"),
+        ];
+        for (headers, expected) in cases.iter() {
+            let mut branch = BranchStatus::default();
+            for header in headers.iter() {
+                branch.parse(header);
+            }
+            assert_eq!(branch.header(), *expected);
+        }
+    }
+
+    #[test]
+    fn lists_pruned_branches() {
+        let output = "From /tmp/origin\n - [deleted]         (none)     -> origin/gone1\n - [deleted]         (none)     -> origin/feature/gone2\n   bb25dca..0e08237  main       -> origin/main\n * [new branch]      topic      -> origin/topic\n";
+        assert_eq!(pruned_branches(output), vec!["origin/gone1", "origin/feature/gone2"]);
+        assert!(pruned_branches("").is_empty());
+    }
+
+    #[test]
+    fn parses_rebase_todo() {
+        let todo = "pick 6feb77d c3\nr 03f4023 fix: the message, with spaces\nfixup 891e043 c5\nupdate-ref refs/heads/mid\n\ndrop 10eeefb c6\n\n# Rebase 2f0c8a1..10eeefb onto 2f0c8a1 (4 commands)\n#\n# Commands:\n# p, pick <commit> = use commit\n";
+        let entries: Vec<_> = parse_rebase_todo(todo).into_iter().map(|e| (e.action, e.hash, e.message)).collect();
+        assert_eq!(
+            entries,
+            vec![
+                (RebaseAction::Pick, "6feb77d".into(), "c3".into()),
+                (RebaseAction::Reword, "03f4023".into(), "fix: the message, with spaces".into()),
+                (RebaseAction::Fixup, "891e043".into(), "c5".into()),
+                (RebaseAction::Other("update-ref".into()), "refs/heads/mid".into(), String::new()),
+                (RebaseAction::Drop, "10eeefb".into(), "c6".into()),
+            ]
+        );
+
+        assert!(parse_rebase_todo("noop\n\n# Rebase 10eeefb..10eeefb onto 10eeefb (1 command)\n").is_empty());
+    }
+
+    #[test]
+    fn parses_blame() {
+        let output = concat!(
+            "995fdf173aa085bc54b7d8e61a2576ef47669596 1 1 1\n",
+            "author a\n",
+            "author-mail <a@b>\n",
+            "author-time 1792172328\n",
+            "author-tz +0000\n",
+            "committer a\n",
+            "committer-mail <a@b>\n",
+            "committer-time 1792172328\n",
+            "committer-tz +0000\n",
+            "summary init\n",
+            "boundary\n",
+            "filename b\n",
+            "\tb\n",
+            "0000000000000000000000000000000000000000 2 2 2\n",
+            "author Not Committed Yet\n",
+            "author-mail <not.committed.yet>\n",
+            "author-time 1792172389\n",
+            "author-tz +0000\n",
+            "committer Not Committed Yet\n",
+            "committer-mail <not.committed.yet>\n",
+            "committer-time 1792172389\n",
+            "committer-tz +0000\n",
+            "summary Version of b from b\n",
+            "previous 0e08237a3f79c2c3fd21aba67d32a022b57dfabf b\n",
+            "filename b\n",
+            "\tz\n",
+            "0000000000000000000000000000000000000000 3 3\n",
+            "author Not Committed Yet\n",
+            "author-mail <not.committed.yet>\n",
+            "summary Version of b from b\n",
+            "filename b\n",
+            "\t\tindented\n",
+        );
+        let entries: Vec<_> = parse_blame(output).into_iter().map(|e| (e.hash, e.author, e.line)).collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("995fdf17".into(), "a".into(), "b".into()),
+                ("00000000".into(), "Not Committed Yet".into(), "z".into()),
+                ("00000000".into(), "Not Committed Yet".into(), "\tindented".into()),
+            ]
+        );
+    }
+}
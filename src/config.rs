@@ -0,0 +1,222 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::backend::MergeStrategy;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub merge_strategy: MergeStrategy,
+    pub commit_verbose: bool,
+    pub git_path: String,
+    pub auto_fetch: bool,
+    pub refresh_interval: Option<Duration>,
+    pub expert_mode: bool,                 // skips confirmations
+    pub process_timeout: Option<Duration>, // git commands running longer are killed
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            merge_strategy: MergeStrategy::NoFastForward,
+            commit_verbose: true,
+            git_path: "git".into(),
+            auto_fetch: false,
+            refresh_interval: None,
+            expert_mode: false,
+            process_timeout: None,
+        }
+    }
+}
+
+pub const REPOSITORY_CONFIG_FILE_NAME: &str = ".verco.toml";
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else {
+        match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(config_dir.join("verco").join("config.toml"))
+}
+
+// strips quotes from strings and a trailing comment from everything else
+fn parse_value(value: &str) -> &str {
+    match value.strip_prefix('"') {
+        Some(value) => value.split('"').next().unwrap_or(""),
+        None => value.split('#').next().unwrap_or("").trim(),
+    }
+}
+
+// zero seconds means none
+fn parse_seconds(value: &str) -> Option<Option<Duration>> {
+    match value.parse::<u64>() {
+        Ok(0) => Some(None),
+        Ok(seconds) => Some(Some(Duration::from_secs(seconds))),
+        Err(_) => None,
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl Config {
+    // user config, then the repository config, then environment variables, each overriding the previous
+    // also returns warnings for whatever got ignored
+    pub fn load(repository_root: &Path) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut warnings = Vec::new();
+        if let Some(path) = user_config_path() {
+            config.read_file(&path, true, &mut warnings);
+        }
+        config.read_file(&repository_root.join(REPOSITORY_CONFIG_FILE_NAME), false, &mut warnings);
+        config.read_env(&mut warnings);
+        (config, warnings)
+    }
+
+    // the repository has to be found before its config can be read, and finding it needs the git executable
+    pub fn git_path() -> String {
+        let mut config = Self::default();
+        // reported by 'load' later
+        let mut warnings = Vec::new();
+        if let Some(path) = user_config_path() {
+            config.read_file(&path, true, &mut warnings);
+        }
+        config.read_env(&mut warnings);
+        config.git_path
+    }
+
+    fn read_file(&mut self, path: &Path, is_user_config: bool, warnings: &mut Vec<String>) {
+        // a missing file just keeps the defaults
+        if let Ok(text) = fs::read_to_string(path) {
+            for warning in self.parse(&text, is_user_config) {
+                warnings.push(format!("{}: {}", path.display(), warning));
+            }
+        }
+    }
+
+    // a small subset of toml: one 'key = value' per line, returns warnings for the lines it ignored
+    fn parse(&mut self, text: &str, is_user_config: bool) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), parse_value(value.trim())),
+                None => {
+                    warnings.push(format!("line {}: expected 'key = value', ignored", line_number));
+                    continue;
+                }
+            };
+
+            let valid = match key {
+                "merge" => MergeStrategy::from_name(value).map(|strategy| self.merge_strategy = strategy).is_some(),
+                // a cloned repository should not choose which executable gets run
+                "git" if !is_user_config => {
+                    warnings.push(format!("line {}: 'git' can only be set in the user config", line_number));
+                    continue;
+                }
+                "git" if !value.is_empty() => {
+                    self.git_path = value.into();
+                    true
+                }
+                "git" => false,
+                "commit_verbose" => parse_bool(value).map(|verbose| self.commit_verbose = verbose).is_some(),
+                "auto_fetch" => parse_bool(value).map(|auto_fetch| self.auto_fetch = auto_fetch).is_some(),
+                "refresh_interval" => parse_seconds(value).map(|interval| self.refresh_interval = interval).is_some(),
+                "expert_mode" => parse_bool(value).map(|expert_mode| self.expert_mode = expert_mode).is_some(),
+                "process_timeout" => parse_seconds(value).map(|timeout| self.process_timeout = timeout).is_some(),
+                _ => {
+                    warnings.push(format!("line {}: unknown key '{}' ignored", line_number, key));
+                    continue;
+                }
+            };
+            if !valid {
+                warnings.push(format!("line {}: invalid '{}' value '{}' ignored", line_number, key, value));
+            }
+        }
+        warnings
+    }
+
+    fn read_env(&mut self, warnings: &mut Vec<String>) {
+        if let Ok(value) = env::var("VERCO_MERGE") {
+            match MergeStrategy::from_name(&value) {
+                Some(strategy) => self.merge_strategy = strategy,
+                None => warnings.push(format!("invalid VERCO_MERGE value '{}'", value)),
+            }
+        }
+
+        if let Ok(value) = env::var("VERCO_GIT") {
+            if !value.is_empty() {
+                self.git_path = value;
+            }
+        }
+
+        if let Ok(value) = env::var("VERCO_COMMIT_VERBOSE") {
+            self.commit_verbose = value != "0";
+        }
+
+        if let Ok(value) = env::var("VERCO_AUTO_FETCH") {
+            self.auto_fetch = value == "1";
+        }
+
+        if let Ok(value) = env::var("VERCO_REFRESH_INTERVAL") {
+            match parse_seconds(&value) {
+                Some(interval) => self.refresh_interval = interval,
+                None => warnings.push(format!("invalid VERCO_REFRESH_INTERVAL value '{}'", value)),
+            }
+        }
+
+        if let Ok(value) = env::var("VERCO_EXPERT_MODE") {
+            self.expert_mode = value == "1";
+        }
+
+        if let Ok(value) = env::var("VERCO_PROCESS_TIMEOUT") {
+            match parse_seconds(&value) {
+                Some(timeout) => self.process_timeout = timeout,
+                None => warnings.push(format!("invalid VERCO_PROCESS_TIMEOUT value '{}'", value)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ignores_unknown_keys() {
+        let text = r#"
+# comment
+merge = "ff-only"
+auto_fetch = true # trailing comment
+refresh_interval = 30
+page_size = 50
+expert_mode = yes
+git = "/usr/local/bin/git"
+"#;
+        let mut config = Config::default();
+        let warnings = config.parse(text, false);
+
+        assert!(matches!(config.merge_strategy, MergeStrategy::FastForwardOnly));
+        assert!(config.auto_fetch);
+        assert_eq!(config.refresh_interval, Some(Duration::from_secs(30)));
+        assert!(!config.expert_mode);
+        assert_eq!(config.git_path, "git");
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("'page_size'"));
+    }
+}
@@ -1,57 +1,96 @@
-use std::{env, io, io::Write};
+use std::{env, io, io::Write, path::Path};
 
 #[macro_use]
 mod macros;
 mod application;
 mod backend;
+mod config;
 mod mode;
 mod platform;
+mod recent;
 mod tool;
 mod ui;
 
 fn main() {
     tool::log_init();
 
-    let mut args = env::args();
-    args.next();
-    if let Some(arg) = args.next() {
-        if args.next().is_some() {
-            eprintln!("too many args");
-        } else {
-            match &arg[..] {
-                "-h" | "--help" => {
-                    let name = env!("CARGO_PKG_NAME");
-                    let version = env!("CARGO_PKG_VERSION");
-                    println!("{} v{}", name, version);
-                    println!();
-                    println!("{}", env!("CARGO_PKG_DESCRIPTION"));
-                    println!();
-                    println!("\t-h --help\tprint this help message and exit");
-                    println!("\t-v --version\tprint version number and exit");
-                }
-                "-v" | "--version" => {
-                    print!("{}", env!("CARGO_PKG_VERSION"));
+    let mut mode_kind = None;
+    let mut repository_path = None;
+    for arg in env::args().skip(1) {
+        match &arg[..] {
+            "-h" | "--help" => {
+                let name = env!("CARGO_PKG_NAME");
+                let version = env!("CARGO_PKG_VERSION");
+                println!("{} v{}", name, version);
+                println!();
+                println!("{}", env!("CARGO_PKG_DESCRIPTION"));
+                println!();
+                print_usage();
+                println!();
+                println!("\t-h --help\tprint this help message and exit");
+                println!("\t-v --version\tprint version number and exit");
+                println!();
+                println!("config files, in 'key = value' lines:");
+                println!("\t~/.config/verco/config.toml\tuser config");
+                println!("\t{}\tat the repository root, overrides the user config", config::REPOSITORY_CONFIG_FILE_NAME);
+                println!("\tkeys: merge, git (user config only), commit_verbose, auto_fetch, refresh_interval, expert_mode,");
+                println!("\t\tprocess_timeout");
+                println!("\tthey take the same values as the matching environment variables, booleans as true/false");
+                println!();
+                println!("environment variables, override the config files:");
+                println!("\tVERCO_MERGE\tmerge strategy: 'no-ff' (default), 'ff' or 'ff-only'");
+                println!("\tVERCO_GIT\tpath to the git executable (default 'git')");
+                println!("\tVERCO_COMMIT_VERBOSE\tset to '0' to not show the diff when committing in the editor");
+                println!("\tVERCO_AUTO_FETCH\tset to '1' to fetch in the background on startup");
+                println!("\tVERCO_REFRESH_INTERVAL\tseconds between refreshes of the current list (default '0', never)");
+                println!("\tVERCO_EXPERT_MODE\tset to '1' to not ask for confirmation before destructive actions");
+                println!("\tVERCO_PROCESS_TIMEOUT\tseconds after which a git command is killed (default '0', never)");
+                println!("\tVERCO_LOG\tset to '1' to write a debug log");
+                println!("\tVERCO_LOG_FILE\tdebug log file path (default 'verco.log')");
+                println!("\tGIT_DIR, GIT_WORK_TREE\tare honored for bare repositories or a separate git dir");
+                return;
+            }
+            "-v" | "--version" => {
+                print!("{}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            arg => {
+                // a mode name wins over a directory with the same name
+                if let Some(kind) = mode::ModeKind::from_name(arg) {
+                    if mode_kind.replace(kind).is_some() {
+                        eprintln!("too many args");
+                        return;
+                    }
+                } else if Path::new(arg).is_dir() {
+                    if repository_path.replace(arg.to_string()).is_some() {
+                        eprintln!("too many args");
+                        return;
+                    }
+                } else {
+                    eprintln!("invalid argument '{}'", arg);
+                    print_usage();
+                    return;
                 }
-                arg => eprintln!("invalid argument '{}'", arg),
             }
         }
-        return;
     }
 
-    let (root, backend) = match backend::backend_from_current_repository() {
-        Some((root, backend)) => (root, backend),
-        None => {
-            eprintln!("no repository found");
+    if let Some(path) = repository_path {
+        if env::set_current_dir(&path).is_err() {
+            eprintln!("could not set current dir to {:?}", path);
             return;
         }
-    };
+    }
 
-    if std::env::set_current_dir(&root).is_err() {
-        eprintln!("could not set current dir to {:?}", root);
+    let git_path = config::Config::git_path();
+    let detected = backend::backend_from_current_repository(&git_path);
+    let recent_repositories = if detected.is_none() { recent::load() } else { Vec::new() };
+    if detected.is_none() && recent_repositories.is_empty() {
+        eprintln!("no repository found");
         return;
     }
 
-    let (platform, platform_event_reader) = match platform::Platform::new() {
+    let (platform, mut platform_event_reader) = match platform::Platform::new() {
         Some(platform) => platform,
         None => return,
     };
@@ -60,15 +99,41 @@ fn main() {
         let stdout = io::stdout();
         let mut stdout = stdout.lock();
 
-        stdout.write_all(ui::BEGIN_TITLE_CODE).unwrap();
-        stdout.write_all(root.as_os_str().to_string_lossy().as_bytes()).unwrap();
-        stdout.write_all(ui::END_TITLE_CODE).unwrap();
         stdout.write_all(ui::ENTER_ALTERNATE_BUFFER_CODE).unwrap();
         stdout.write_all(ui::HIDE_CURSOR_CODE).unwrap();
         stdout.flush().unwrap();
     }
 
-    application::run(platform_event_reader, backend);
+    let detected = match detected {
+        Some(detected) => Some(detected),
+        None => recent::pick(&mut platform_event_reader, &recent_repositories).and_then(|path| {
+            env::set_current_dir(path).ok()?;
+            backend::backend_from_current_repository(&git_path)
+        }),
+    };
+
+    let mut config_warnings = Vec::new();
+    if let Some((root, backend)) = detected {
+        recent::add(&root);
+
+        let (config, warnings) = config::Config::load(&root);
+        config_warnings = warnings;
+
+        if env::set_current_dir(&root).is_ok() {
+            {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+
+                stdout.write_all(ui::BEGIN_TITLE_CODE).unwrap();
+                stdout.write_all(root.as_os_str().to_string_lossy().as_bytes()).unwrap();
+                stdout.write_all(ui::END_TITLE_CODE).unwrap();
+                stdout.flush().unwrap();
+            }
+
+            backend::set_process_timeout(config.process_timeout);
+            application::run(platform_event_reader, backend, config, mode_kind.unwrap_or_default());
+        }
+    }
 
     {
         let stdout = io::stdout();
@@ -81,4 +146,16 @@ fn main() {
     }
 
     drop(platform);
+
+    // printed only now as the alternate screen would have hidden them
+    for warning in config_warnings {
+        eprintln!("{}", warning);
+    }
+}
+
+fn print_usage() {
+    let name = env!("CARGO_PKG_NAME");
+    println!("usage: {} [<mode>] [<repository path>]", name);
+    println!();
+    println!("modes: {} (default: {})", mode::ModeKind::NAMES.join(", "), mode::ModeKind::NAMES[0]);
 }
@@ -1,12 +1,23 @@
 use bounded_vec_deque::BoundedVecDeque;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use crate::{application::EventSender, backend::Backend, platform::Key, tool::*, ui::Drawer};
+use crate::{
+    application::EventSender,
+    backend::{Backend, BackendResult, RevisionEntry},
+    config::Config,
+    platform::Key,
+    tool::*,
+    ui::Drawer,
+};
 
 pub mod branches;
 pub mod diff;
+pub mod file_view;
+pub mod history;
+pub mod hunks;
 pub mod log;
 pub mod message_input;
+pub mod rebase;
 pub mod revision_details;
 pub mod stash;
 pub mod stash_details;
@@ -23,6 +34,9 @@ pub enum ModeResponse {
     Diff(diff::Response),
     StashDetails(stash_details::Response),
     _MessageInput(message_input::Response),
+    FileView(file_view::Response),
+    Rebase(rebase::Response),
+    Hunks(hunks::Response),
 }
 impl ModeResponse {
     pub fn mode_kind(&self) -> ModeKind {
@@ -36,6 +50,9 @@ impl ModeResponse {
             ModeResponse::Diff(_) => ModeKind::Diff,
             ModeResponse::StashDetails(_) => ModeKind::StashDetails,
             ModeResponse::_MessageInput(_) => ModeKind::MessageInput,
+            ModeResponse::FileView(_) => ModeKind::FileView,
+            ModeResponse::Rebase(_) => ModeKind::Rebase,
+            ModeResponse::Hunks(_) => ModeKind::Hunks,
         }
     }
 }
@@ -51,6 +68,10 @@ pub enum Mode {
     Diff(diff::Mode),
     StashDetails(stash_details::Mode),
     MessageInput(message_input::Mode),
+    History(history::Mode),
+    FileView(file_view::Mode),
+    Rebase(rebase::Mode),
+    Hunks(hunks::Mode),
 }
 impl Default for Mode {
     fn default() -> Self {
@@ -70,6 +91,10 @@ impl Mode {
             ModeKind::Diff => Self::Diff(diff::Mode::default()),
             ModeKind::StashDetails => Self::StashDetails(stash_details::Mode::default()),
             ModeKind::MessageInput => Self::MessageInput(message_input::Mode::default()),
+            ModeKind::History => Self::History(history::Mode::default()),
+            ModeKind::FileView => Self::FileView(file_view::Mode::default()),
+            ModeKind::Rebase => Self::Rebase(rebase::Mode::default()),
+            ModeKind::Hunks => Self::Hunks(hunks::Mode::default()),
         }
     }
 
@@ -84,6 +109,10 @@ impl Mode {
             Self::Diff(mode) => mode,
             Self::StashDetails(mode) => mode,
             Self::MessageInput(mode) => mode,
+            Self::History(mode) => mode,
+            Self::FileView(mode) => mode,
+            Self::Rebase(mode) => mode,
+            Self::Hunks(mode) => mode,
         }
     }
 
@@ -98,6 +127,10 @@ impl Mode {
             Self::Diff(_) => ModeKind::Diff,
             Self::StashDetails(_) => ModeKind::StashDetails,
             Self::MessageInput(_) => ModeKind::MessageInput,
+            Self::History(_) => ModeKind::History,
+            Self::FileView(_) => ModeKind::FileView,
+            Self::Rebase(_) => ModeKind::Rebase,
+            Self::Hunks(_) => ModeKind::Hunks,
         }
     }
 }
@@ -149,6 +182,11 @@ pub enum ModeInfo {
     RevisionDetails(String),
     StashDetails(usize),
     MessageInput(message_input::ModeInfo),
+    FileView((String, String)),
+    Rebase(String),
+    Diff(diff::DiffRequest),
+    Hunks(Vec<RevisionEntry>),
+    Undoable,
 }
 
 impl ModeChangeInfo {
@@ -160,6 +198,27 @@ impl ModeChangeInfo {
         Self { from, info: Some(ModeInfo::RevisionDetails(revision)) }
     }
 
+    pub fn file(from: ModeKind, revision: String, path: String) -> Self {
+        Self { from, info: Some(ModeInfo::FileView((revision, path))) }
+    }
+
+    pub fn rebase(from: ModeKind, base: String) -> Self {
+        Self { from, info: Some(ModeInfo::Rebase(base)) }
+    }
+
+    pub fn diff(from: ModeKind, request: diff::DiffRequest) -> Self {
+        Self { from, info: Some(ModeInfo::Diff(request)) }
+    }
+
+    pub fn hunks(from: ModeKind, entries: Vec<RevisionEntry>) -> Self {
+        Self { from, info: Some(ModeInfo::Hunks(entries)) }
+    }
+
+    // entered right after an operation that can be undone from the entered mode
+    pub fn undoable(from: ModeKind) -> Self {
+        Self { from, info: Some(ModeInfo::Undoable) }
+    }
+
     pub fn stash(from: ModeKind, stash_id: usize) -> Self {
         Self { from, info: Some(ModeInfo::StashDetails(stash_id)) }
     }
@@ -170,10 +229,43 @@ impl ModeChangeInfo {
         placeholder: S,
         on_submit: fn(&ModeContext, String),
     ) -> Self {
-        Self {
-            from,
-            info: Some(ModeInfo::MessageInput(message_input::ModeInfo::new(not_empty, placeholder.into(), on_submit))),
+        Self::message_input_with_text(from, not_empty, placeholder, String::new(), on_submit)
+    }
+
+    // marks a message input as a commit message
+    pub fn commit_message(mut self) -> Self {
+        if let Some(ModeInfo::MessageInput(info)) = &mut self.info {
+            info.commit_message = true;
+        }
+        self
+    }
+
+    // shows why the previous submit of the same input failed
+    pub fn error(mut self, error: String) -> Self {
+        if let Some(ModeInfo::MessageInput(info)) = &mut self.info {
+            info.error = error;
+        }
+        self
+    }
+
+    // lists what the submit will act on below the input
+    pub fn preview(mut self, preview: String) -> Self {
+        if let Some(ModeInfo::MessageInput(info)) = &mut self.info {
+            info.preview = preview;
         }
+        self
+    }
+
+    pub fn message_input_with_text<S: Into<String>>(
+        from: ModeKind,
+        not_empty: bool,
+        placeholder: S,
+        text: String,
+        on_submit: fn(&ModeContext, String),
+    ) -> Self {
+        let mut info = message_input::ModeInfo::new(not_empty, placeholder.into(), on_submit);
+        info.text = text;
+        Self { from, info: Some(ModeInfo::MessageInput(info)) }
     }
 }
 
@@ -188,18 +280,51 @@ pub enum ModeKind {
     Diff,
     StashDetails,
     MessageInput,
+    History,
+    FileView,
+    Rebase,
+    Hunks,
 }
 impl Default for ModeKind {
     fn default() -> Self {
         Self::Status
     }
 }
+impl ModeKind {
+    // the modes that can be opened from the command line, the default one first
+    pub const NAMES: [&'static str; 6] = ["status", "log", "branches", "tags", "stash", "history"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "status" => Some(Self::Status),
+            "log" => Some(Self::Log),
+            "branches" => Some(Self::Branches),
+            "tags" => Some(Self::Tags),
+            "stash" => Some(Self::Stash),
+            "history" => Some(Self::History),
+            _ => None,
+        }
+    }
+}
 
 pub trait ModeTrait {
     fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo);
+    // requests the list again without resetting what is on screen, for the periodic refresh
+    fn refresh(&mut self, ctx: &ModeContext) {
+        // output on screen, like an operation result or an error, stays until the user moves on
+        if !self.is_waiting_response() && !self.is_showing_output() {
+            self.request_refresh(ctx);
+        }
+    }
+    // whether output is shown in place of the list
+    fn is_showing_output(&self) -> bool {
+        false
+    }
+    fn request_refresh(&mut self, _ctx: &ModeContext) {}
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus;
     fn is_waiting_response(&self) -> bool;
     fn on_response(&mut self, ctx: &ModeContext, response: ModeResponse);
+    fn on_resize(&mut self, size: (u16, u16));
     fn header(&self) -> (&str, &str, &str);
     fn draw(&self, drawer: &mut Drawer);
 }
@@ -207,8 +332,59 @@ pub trait ModeTrait {
 #[derive(Clone)]
 pub struct ModeContext {
     pub backend: Arc<dyn Backend>,
+    pub config: Arc<Config>,
     pub event_sender: EventSender,
     pub viewport_size: (u16, u16),
+    pub last_output: Arc<Mutex<String>>, // what the last operation printed, shown in history
+    pub toggles: Arc<Mutex<Toggles>>,
+}
+impl ModeContext {
+    // keeps the output of an operation around after the mode replaces it
+    pub fn record_output(&self, result: BackendResult<String>) -> BackendResult<()> {
+        let output = match &result {
+            Ok(output) => output,
+            Err(error) => error,
+        };
+        *self.last_output.lock().unwrap() = output.clone();
+        result.map(|_| ())
+    }
+}
+
+// on/off switches that last the whole session, shown in the status line at the bottom
+#[derive(Default, Clone, Debug)]
+pub struct Toggles {
+    pub all_refs: bool,
+    pub hide_merges: bool,
+    pub first_parent: bool,
+    pub ignore_whitespace: bool,
+    pub mark_whitespace: bool,
+}
+impl Toggles {
+    pub fn entries(&self) -> [(&'static str, bool); 5] {
+        [
+            ("all refs", self.all_refs),
+            ("hide merges", self.hide_merges),
+            ("first parent", self.first_parent),
+            ("ignore whitespace", self.ignore_whitespace),
+            ("mark whitespace", self.mark_whitespace),
+        ]
+    }
+}
+
+#[cfg(test)]
+impl ModeContext {
+    pub fn for_test(backend: Arc<crate::backend::fake::FakeBackend>) -> (Self, crate::application::EventReceiver) {
+        let (event_sender, event_receiver) = EventSender::channel();
+        let ctx = Self {
+            backend,
+            config: Arc::new(Config::default()),
+            event_sender,
+            viewport_size: (80, 24),
+            last_output: Arc::default(),
+            toggles: Arc::default(),
+        };
+        (ctx, event_receiver)
+    }
 }
 
 pub struct ModeStatus {
@@ -222,12 +398,6 @@ pub struct Output {
     scroll: usize,
 }
 impl Output {
-    pub fn new(text: String) -> Self {
-        let mut output = Output::default();
-        output.set(text);
-        output
-    }
-
     pub fn set(&mut self, output: String) {
         self.text = output;
         self.line_count = self.text.lines().count();
@@ -242,25 +412,39 @@ impl Output {
         self.line_count
     }
 
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
     pub fn lines_from_scroll<'a>(&'a self) -> impl 'a + Iterator<Item = &'a str> {
         self.text.lines().skip(self.scroll)
     }
 
-    pub fn on_key(&mut self, available_height: usize, key: Key) {
+    pub fn on_key(&mut self, available_height: usize, count: usize, key: Key) {
         let half_height = available_height / 2;
 
         self.scroll = match key {
-            Key::Down | Key::Char('j') => self.scroll + 1,
-            Key::Up | Key::Char('k') => self.scroll.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.scroll.saturating_add(count),
+            Key::Up | Key::Char('k') => self.scroll.saturating_sub(count),
             Key::Ctrl('h') | Key::Home => 0,
             Key::Ctrl('e') | Key::End => usize::MAX,
-            Key::Ctrl('d') | Key::PageDown => self.scroll + half_height,
-            Key::Ctrl('u') | Key::PageUp => self.scroll.saturating_sub(half_height),
+            Key::Ctrl('d') | Key::PageDown => self.scroll.saturating_add(half_height.saturating_mul(count)),
+            Key::Ctrl('u') | Key::PageUp => self.scroll.saturating_sub(half_height.saturating_mul(count)),
             _ => self.scroll,
         };
 
+        self.on_resize(available_height);
+    }
+
+    pub fn on_resize(&mut self, available_height: usize) {
         self.scroll = self.line_count.saturating_sub(available_height).min(self.scroll);
     }
+
+    // puts 'line' at the top so it's visible no matter how the lines above it wrap
+    pub fn scroll_to(&mut self, line: usize, available_height: usize) {
+        self.scroll = line;
+        self.on_resize(available_height);
+    }
 }
 
 #[derive(Default, Clone, Debug)]
@@ -272,6 +456,11 @@ impl ReadLine {
         self.input.clear();
     }
 
+    pub fn set(&mut self, input: &str) {
+        self.input.clear();
+        self.input.push_str(input);
+    }
+
     pub fn input(&self) -> &str {
         &self.input
     }
@@ -314,6 +503,31 @@ impl ReadLine {
     }
 }
 
+// vim style repeat count typed as digits before a movement key
+#[derive(Default, Clone, Debug)]
+pub struct Count {
+    pending: usize,
+}
+impl Count {
+    // returns true if 'key' was a digit and got added to the pending count
+    pub fn on_key(&mut self, key: Key) -> bool {
+        match key {
+            // a leading '0' is not a count
+            Key::Char(c @ '0'..='9') if c != '0' || self.pending > 0 => {
+                let digit = c as usize - '0' as usize;
+                self.pending = self.pending.saturating_mul(10).saturating_add(digit);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // returns the pending count, or 1 if there is none, and resets it
+    pub fn take(&mut self) -> usize {
+        std::mem::take(&mut self.pending).max(1)
+    }
+}
+
 pub enum SelectMenuAction {
     None,
     Toggle(usize),
@@ -336,26 +550,30 @@ impl SelectMenu {
         }
     }
 
-    pub fn on_key(&mut self, entries_len: usize, available_height: usize, key: Key) -> SelectMenuAction {
+    pub fn on_resize(&mut self, entries_len: usize, available_height: usize) {
+        self.saturate_cursor(entries_len);
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + available_height {
+            self.scroll = self.cursor + 1 - available_height;
+        }
+    }
+
+    pub fn on_key(&mut self, entries_len: usize, available_height: usize, count: usize, key: Key) -> SelectMenuAction {
         let half_height = available_height / 2;
 
         self.cursor = match key {
-            Key::Down | Key::Ctrl('n') | Key::Char('j') => self.cursor + 1,
-            Key::Up | Key::Ctrl('p') | Key::Char('k') => self.cursor.saturating_sub(1),
+            Key::Down | Key::Ctrl('n') | Key::Char('j') => self.cursor.saturating_add(count),
+            Key::Up | Key::Ctrl('p') | Key::Char('k') => self.cursor.saturating_sub(count),
             Key::Ctrl('h') | Key::Home => 0,
             Key::Ctrl('e') | Key::End => usize::MAX,
-            Key::Ctrl('d') | Key::PageDown => self.cursor + half_height,
-            Key::Ctrl('u') | Key::PageUp => self.cursor.saturating_sub(half_height),
+            Key::Ctrl('d') | Key::PageDown => self.cursor.saturating_add(half_height.saturating_mul(count)),
+            Key::Ctrl('u') | Key::PageUp => self.cursor.saturating_sub(half_height.saturating_mul(count)),
             _ => self.cursor,
         };
 
-        self.saturate_cursor(entries_len);
-
-        if self.cursor < self.scroll {
-            self.scroll = self.cursor;
-        } else if self.cursor >= self.scroll + available_height {
-            self.scroll = self.cursor + 1 - available_height;
-        }
+        self.on_resize(entries_len, available_height);
 
         match key {
             Key::Char(' ') if self.cursor < entries_len => SelectMenuAction::Toggle(self.cursor),
@@ -402,25 +620,37 @@ impl Filter {
     where
         I: 'entries + Iterator<Item = &'entries E>,
         E: 'entries + FilterEntry,
+    {
+        self.filter_by(entries, |_| true);
+    }
+
+    // like 'filter' but entries also need to pass 'predicate' to be visible
+    pub fn filter_by<'entries, I, E, P>(&mut self, entries: I, predicate: P)
+    where
+        I: 'entries + Iterator<Item = &'entries E>,
+        E: 'entries + FilterEntry,
+        P: Fn(&E) -> bool,
     {
         self.visible_indices.clear();
         for (i, entry) in entries.enumerate() {
-            if entry.fuzzy_matches(self.as_str()) {
+            if predicate(entry) && entry.fuzzy_matches(self.as_str()) {
                 self.visible_indices.push(i);
             }
         }
     }
 
-    pub fn on_remove_entry(&mut self, entry_index: usize) {
-        for i in (0..self.visible_indices.len()).rev() {
-            if entry_index < i {
-                self.visible_indices[i] -= 1;
-            } else if entry_index == i {
-                self.visible_indices.remove(i);
-            } else {
-                break;
+    // returns the position the removed entry had among the visible ones
+    pub fn on_remove_entry(&mut self, entry_index: usize) -> Option<usize> {
+        let position = self.visible_indices.iter().position(|&i| i == entry_index);
+        if let Some(position) = position {
+            self.visible_indices.remove(position);
+        }
+        for i in &mut self.visible_indices {
+            if *i > entry_index {
+                *i -= 1;
             }
         }
+        position
     }
 
     pub fn get_visible_index(&self, index: usize) -> Option<usize> {
@@ -472,3 +702,85 @@ pub fn fuzzy_matches(text: &str, pattern: &str) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FileStatus, RevisionEntry};
+
+    fn filtered(pattern: &str) -> Filter {
+        let entries: Vec<_> = ["src/main.rs", "README.md", "src/mode.rs", "Cargo.toml", "src/ui.rs"]
+            .iter()
+            .map(|name| RevisionEntry::new((*name).into(), FileStatus::Modified))
+            .collect();
+
+        let mut filter = Filter::default();
+        filter.enter();
+        for c in pattern.chars() {
+            filter.on_key(Key::Char(c));
+        }
+        filter.filter(entries.iter());
+        filter
+    }
+
+    #[test]
+    fn filter_remove_hidden_entry() {
+        let mut filter = filtered("src");
+        assert_eq!(filter.visible_indices(), [0, 2, 4]);
+
+        assert_eq!(filter.on_remove_entry(1), None);
+        assert_eq!(filter.visible_indices(), [0, 1, 3]);
+    }
+
+    #[test]
+    fn filter_remove_visible_entry() {
+        let mut filter = filtered("src");
+
+        assert_eq!(filter.on_remove_entry(2), Some(1));
+        assert_eq!(filter.visible_indices(), [0, 3]);
+
+        assert_eq!(filter.on_remove_entry(3), Some(1));
+        assert_eq!(filter.visible_indices(), [0]);
+
+        assert_eq!(filter.on_remove_entry(0), Some(0));
+        assert!(filter.visible_indices().is_empty());
+    }
+
+    #[test]
+    fn filter_remove_entries_in_reverse() {
+        let mut filter = filtered("");
+        assert_eq!(filter.visible_indices(), [0, 1, 2, 3, 4]);
+
+        assert_eq!(filter.on_remove_entry(3), Some(3));
+        assert_eq!(filter.on_remove_entry(1), Some(1));
+        assert_eq!(filter.visible_indices(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn mode_names_round_trip() {
+        assert_eq!(ModeKind::from_name(ModeKind::NAMES[0]), Some(ModeKind::default()));
+        for name in ModeKind::NAMES {
+            assert!(ModeKind::from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(ModeKind::from_name("diff"), None);
+    }
+
+    #[test]
+    fn count_repeats_movement() {
+        let mut count = Count::default();
+        let mut select = SelectMenu::default();
+        for c in "012".chars() {
+            let consumed = count.on_key(Key::Char(c));
+            assert_eq!(consumed, c != '0');
+        }
+        select.on_key(20, 10, count.take(), Key::Char('j'));
+        assert_eq!(select.cursor, 12);
+
+        assert!(count.on_key(Key::Char('3')));
+        select.on_key(20, 10, count.take(), Key::Char('k'));
+        assert_eq!(select.cursor, 9);
+
+        select.on_key(20, 10, count.take(), Key::Char('k'));
+        assert_eq!(select.cursor, 8);
+    }
+}
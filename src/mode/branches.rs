@@ -1,17 +1,22 @@
-use std::thread;
+use std::{cmp::Reverse, thread};
 
 use crate::{
     backend::{Backend, BackendResult, BranchEntry},
     mode::*,
     platform::Key,
-    ui::{Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
+    ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
 };
 
 pub enum Response {
     Refresh(BackendResult<Vec<BranchEntry>>),
     Checkout(usize),
+    // checkout failed because of local changes, they may be stashed to retry
+    CheckoutBlocked(usize, String),
+    // checkout after stashing went through, with the result of popping the stash back
+    StashCheckout(usize, BackendResult<()>),
     New(String),
     Merge,
+    UpstreamCandidates(String, BackendResult<Vec<BranchEntry>>),
 }
 
 #[derive(Clone, Debug)]
@@ -21,6 +26,7 @@ enum WaitOperation {
     Delete,
     Merge,
     Checkout,
+    SetUpstream,
 }
 
 #[derive(Clone, Debug)]
@@ -35,9 +41,18 @@ impl Default for State {
 }
 
 impl SelectEntryDraw for BranchEntry {
-    fn draw(&self, drawer: &mut Drawer, _: bool, _: bool) -> usize {
+    fn draw(&self, drawer: &mut Drawer, hovered: bool, _: bool) -> usize {
         let status = if self.checked_out { " (checked out)" } else { "" };
-        drawer.fmt(format_args!("{}{}", self.name, status));
+        let time = relative_time(self.timestamp);
+        if hovered {
+            drawer.fmt(format_args!("{:>7} {}{}", time, self.name, status));
+        } else {
+            let name_color = if self.remote { Color::DarkRed } else { Color::White };
+            drawer.fmt(format_args!("{}{:>7} {}{}{}{}", Color::DarkBlue, time, name_color, self.name, Color::White, status));
+        }
+        if let Some(upstream) = &self.upstream {
+            drawer.fmt(format_args!(" {}-> {}{}", Color::DarkYellow, upstream, Color::White));
+        }
         1
     }
 }
@@ -48,10 +63,65 @@ pub struct Mode {
     entries: Vec<BranchEntry>,
     output: Output,
     select: SelectMenu,
+    count: Count,
     filter: Filter,
+    show_remotes: bool,
+    sort_by_time: bool,
+    // local branch whose upstream is being picked from the remote branches
+    upstream_for: Option<String>,
+    upstream_candidates: Vec<BranchEntry>,
+    upstream_select: SelectMenu,
+    // local branch entry waiting for the user to choose whether to stash and retry the checkout
+    stash_checkout_for: Option<usize>,
+    // remote branch waiting for a second [d] as deleting it affects everyone using the remote
+    confirm_delete_remote: Option<String>,
 }
 
 impl Mode {
+    fn sort_entries(&mut self) {
+        if self.sort_by_time {
+            self.entries.sort_by_key(|e| (e.remote, Reverse(e.timestamp)));
+        } else {
+            self.entries.sort_by(|a, b| (a.remote, &a.name).cmp(&(b.remote, &b.name)));
+        }
+    }
+
+    fn stash_and_checkout(&mut self, ctx: &ModeContext, entry_index: usize, pop: bool) {
+        self.state = State::Waiting(WaitOperation::Checkout);
+        self.output.set(String::new());
+
+        let name = self.entries[entry_index].name.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let message = format!("before switching to {}", name);
+            // untracked files in the way of the checkout are moved aside as well
+            let result = ctx.backend.stash(&message, &[], true).and_then(|_| match ctx.backend.checkout(&name) {
+                Ok(()) => Ok(()),
+                // the changes go back where they were instead of staying in the stash unnoticed
+                Err(error) => match ctx.backend.stash_pop(0) {
+                    Ok(()) => Err(error),
+                    Err(pop_error) => Err(format!(
+                        "{}\nyour changes are still in the stash as stash@{{0}}, restoring them failed:\n{}",
+                        error, pop_error
+                    )),
+                },
+            });
+            match result {
+                Ok(()) => {
+                    let result = if pop { ctx.backend.stash_pop(0) } else { Ok(()) };
+                    let ok = result.is_ok();
+                    ctx.event_sender.send_response(ModeResponse::Branches(Response::StashCheckout(entry_index, result)));
+                    if ok {
+                        ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Branches));
+                    }
+                }
+                Err(error) => {
+                    ctx.event_sender.send_response(ModeResponse::Branches(Response::Refresh(Err(error))));
+                }
+            }
+        });
+    }
+
     fn set_checkout(&mut self, entry_index: usize) {
         for entry in &mut self.entries {
             entry.checked_out = false;
@@ -72,10 +142,55 @@ impl ModeTrait for Mode {
         self.filter.filter(self.entries.iter());
         self.select.saturate_cursor(self.filter.visible_indices().len());
 
-        request(ctx, |_| Ok(()));
+        request(ctx, self.show_remotes, |_| Ok(()));
+    }
+
+    fn is_showing_output(&self) -> bool {
+        !self.output.text().is_empty()
+    }
+
+    fn request_refresh(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Refresh);
+        request(ctx, self.show_remotes, |_| Ok(()));
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if let Some(entry_index) = self.stash_checkout_for.take() {
+            match key {
+                Key::Char('s') => self.stash_and_checkout(ctx, entry_index, true),
+                Key::Char('S') => self.stash_and_checkout(ctx, entry_index, false),
+                _ => self.output.set(String::new()),
+            }
+            return ModeStatus { pending_input: true };
+        }
+
+        let confirm_delete_remote = self.confirm_delete_remote.take();
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
+
+        if let Some(name) = &self.upstream_for {
+            let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+            self.upstream_select.on_key(self.upstream_candidates.len(), available_height, count, key);
+
+            match key {
+                Key::Esc | Key::Ctrl('c') | Key::Char('q') => self.upstream_for = None,
+                Key::Enter => {
+                    if let Some(upstream) = self.upstream_candidates.get(self.upstream_select.cursor) {
+                        let name = name.clone();
+                        let upstream = upstream.name.clone();
+                        self.state = State::Waiting(WaitOperation::SetUpstream);
+                        request(ctx, self.show_remotes, move |b| b.set_upstream(&name, &upstream));
+                    }
+                    self.upstream_for = None;
+                }
+                _ => (),
+            }
+
+            return ModeStatus { pending_input: true };
+        }
+
         if self.filter.has_focus() {
             self.filter.on_key(key);
             self.filter.filter(self.entries.iter());
@@ -86,9 +201,9 @@ impl ModeTrait for Mode {
 
         let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
         if self.output.text().is_empty() {
-            self.select.on_key(self.filter.visible_indices().len(), available_height, key);
+            self.select.on_key(self.filter.visible_indices().len(), available_height, count, key);
         } else {
-            self.output.on_key(available_height, key);
+            self.output.on_key(available_height, count, key);
         }
 
         let current_entry_index = self.filter.get_visible_index(self.select.cursor);
@@ -102,6 +217,20 @@ impl ModeTrait for Mode {
 
                     if entry.checked_out {
                         ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Branches));
+                    } else if entry.remote {
+                        self.state = State::Waiting(WaitOperation::Checkout);
+
+                        let include_remotes = self.show_remotes;
+                        thread::spawn(move || match ctx.backend.checkout_tracking(&name) {
+                            Ok(()) => {
+                                let result = ctx.backend.branches(include_remotes);
+                                ctx.event_sender.send_response(ModeResponse::Branches(Response::Refresh(result)));
+                                ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Branches));
+                            }
+                            Err(error) => {
+                                ctx.event_sender.send_response(ModeResponse::Branches(Response::Refresh(Err(error))));
+                            }
+                        });
                     } else {
                         self.state = State::Waiting(WaitOperation::Checkout);
 
@@ -111,6 +240,12 @@ impl ModeTrait for Mode {
                                     .send_response(ModeResponse::Branches(Response::Checkout(current_entry_index)));
                                 ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Branches));
                             }
+                            Err(error) if error.contains("would be overwritten by checkout") => {
+                                ctx.event_sender.send_response(ModeResponse::Branches(Response::CheckoutBlocked(
+                                    current_entry_index,
+                                    error,
+                                )));
+                            }
                             Err(error) => {
                                 ctx.event_sender.send_response(ModeResponse::Branches(Response::Refresh(Err(error))));
                             }
@@ -132,16 +267,60 @@ impl ModeTrait for Mode {
             c @ Key::Char('D') | c @ Key::Char('d') => {
                 if let Some(current_entry_index) = current_entry_index {
                     let entry = &self.entries[current_entry_index];
+                    if entry.remote && confirm_delete_remote.as_ref() != Some(&entry.name) && !ctx.config.expert_mode {
+                        self.confirm_delete_remote = Some(entry.name.clone());
+                        return ModeStatus { pending_input: false };
+                    }
                     self.state = State::Waiting(WaitOperation::Delete);
 
                     let name = entry.name.clone();
+                    let remote = entry.remote;
                     self.entries.remove(current_entry_index);
                     self.filter.on_remove_entry(current_entry_index);
                     self.select.on_remove_entry(self.select.cursor);
 
                     let force = c == Key::Char('D'); // D means force delete
 
-                    request(ctx, move |b| b.delete_branch(&name, force));
+                    if remote {
+                        request(ctx, self.show_remotes, move |b| b.delete_remote_branch(&name));
+                    } else {
+                        request(ctx, self.show_remotes, move |b| b.delete_branch(&name, force));
+                    }
+                }
+            }
+            Key::Char('u') => {
+                if let Some(current_entry_index) = current_entry_index {
+                    let entry = &self.entries[current_entry_index];
+                    if !entry.remote {
+                        self.state = State::Waiting(WaitOperation::SetUpstream);
+
+                        let name = entry.name.clone();
+                        let ctx = ctx.clone();
+                        thread::spawn(move || {
+                            let result =
+                                ctx.backend.branches(true).map(|entries| entries.into_iter().filter(|e| e.remote).collect());
+                            ctx.event_sender
+                                .send_response(ModeResponse::Branches(Response::UpstreamCandidates(name, result)));
+                        });
+                    }
+                }
+            }
+            Key::Char('o') => {
+                self.sort_by_time = !self.sort_by_time;
+                self.sort_entries();
+                self.filter.filter(self.entries.iter());
+            }
+            Key::Char('r') => {
+                if let State::Idle = self.state {
+                    self.state = State::Waiting(WaitOperation::Refresh);
+                    self.show_remotes = !self.show_remotes;
+                    request(ctx, self.show_remotes, |_| Ok(()));
+                }
+            }
+            Key::Char('v') => {
+                if let Some(current_entry_index) = current_entry_index {
+                    let request = diff::DiffRequest::MergeBase(self.entries[current_entry_index].name.clone());
+                    ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::Branches, request));
                 }
             }
             Key::Char('m') => {
@@ -151,10 +330,10 @@ impl ModeTrait for Mode {
 
                     let name = entry.name.clone();
                     let ctx = ctx.clone();
-                    thread::spawn(move || match ctx.backend.merge(&name) {
+                    thread::spawn(move || match ctx.record_output(ctx.backend.merge(&name, ctx.config.merge_strategy)) {
                         Ok(()) => {
                             ctx.event_sender.send_response(ModeResponse::Branches(Response::Merge));
-                            ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Branches));
+                            ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::undoable(ModeKind::Branches));
                         }
                         Err(error) => {
                             ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Branches));
@@ -181,7 +360,10 @@ impl ModeTrait for Mode {
                 }
                 if let State::Idle = self.state {
                     match result {
-                        Ok(entries) => self.entries = entries,
+                        Ok(entries) => {
+                            self.entries = entries;
+                            self.sort_entries();
+                        }
                         Err(error) => self.output.set(error),
                     }
                 }
@@ -199,10 +381,47 @@ impl ModeTrait for Mode {
                 self.state = State::Idle;
                 self.set_checkout(entry_index);
             }
+            Response::CheckoutBlocked(entry_index, error) => {
+                self.state = State::Idle;
+                self.output.set(format!(
+                    "{}\npress [s] to stash the changes, checkout and pop them back, [S] to only stash and checkout \
+                     or any other key to cancel",
+                    error.trim_end()
+                ));
+                self.stash_checkout_for = Some(entry_index);
+            }
+            Response::StashCheckout(entry_index, result) => {
+                self.state = State::Idle;
+                self.set_checkout(entry_index);
+                if let Err(error) = result {
+                    self.output.set(format!("switched branches but the stash could not be popped back:\n{}", error));
+                }
+            }
             Response::Merge => self.state = State::Idle,
+            Response::UpstreamCandidates(name, result) => {
+                self.state = State::Idle;
+                match result {
+                    Ok(entries) if entries.is_empty() => self.output.set("there are no remote branches".into()),
+                    Ok(mut entries) => {
+                        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+                        // start at the current upstream when there is one
+                        let upstream =
+                            self.entries.iter().find(|e| !e.remote && e.name == name).and_then(|e| e.upstream.as_ref());
+                        self.upstream_select = SelectMenu::default();
+                        if let Some(i) = upstream.and_then(|u| entries.iter().position(|e| &e.name == u)) {
+                            self.upstream_select.cursor = i;
+                        }
+
+                        self.upstream_candidates = entries;
+                        self.upstream_for = Some(name);
+                    }
+                    Err(error) => self.output.set(error),
+                }
+            }
             Response::New(message) => {
                 self.state = State::Waiting(WaitOperation::New);
-                request(ctx, move |b| b.new_branch(&message));
+                request(ctx, self.show_remotes, move |b| b.new_branch(&message));
             }
         }
     }
@@ -214,6 +433,12 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.select.on_resize(self.filter.visible_indices().len(), available_height);
+        self.output.on_resize(available_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
         let name = match self.state {
             State::Idle | State::Waiting(WaitOperation::Refresh) => "branches",
@@ -221,18 +446,45 @@ impl ModeTrait for Mode {
             State::Waiting(WaitOperation::Delete) => "delete branch",
             State::Waiting(WaitOperation::Merge) => "merge branch",
             State::Waiting(WaitOperation::Checkout) => "checkout",
+            State::Waiting(WaitOperation::SetUpstream) => "set upstream",
         };
-        let (left_help, right_help) =
-            ("[enter]checkout [n]new [d]delete [D]force delete [m]merge", "[arrows]move [ctrl+f]filter");
+        if self.upstream_for.is_some() {
+            return ("set upstream", "[enter]set upstream", "[arrows]move [esc]cancel");
+        }
+        let (left_help, right_help) = (
+            "[enter]checkout [n]new [d]delete [D]force delete [m]merge [u]set upstream",
+            "[v]diff merge base [r]remotes [o]sort [arrows]move [ctrl+f]filter",
+        );
         (name, left_help, right_help)
     }
 
     fn draw(&self, drawer: &mut Drawer) {
+        if self.upstream_for.is_some() {
+            drawer.select_menu(&self.upstream_select, 0, false, self.upstream_candidates.iter());
+            return;
+        }
+
         let filter_line_count = drawer.filter(&self.filter);
         if self.output.text.is_empty() {
+            // a repository without commits has no branch to list yet
+            if self.entries.is_empty() && matches!(self.state, State::Idle) {
+                drawer.fmt(format_args!("{}no branches yet! they show up after the first commit", Color::DarkYellow));
+                return;
+            }
+            let mut line_count = filter_line_count;
+            if let Some(name) = &self.confirm_delete_remote {
+                drawer.fmt(format_args!(
+                    "{}press [d] again to delete '{}' from the remote{}",
+                    Color::DarkYellow,
+                    name,
+                    Color::White
+                ));
+                drawer.next_line();
+                line_count += 1;
+            }
             drawer.select_menu(
                 &self.select,
-                filter_line_count,
+                line_count,
                 false,
                 self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
             );
@@ -242,7 +494,7 @@ impl ModeTrait for Mode {
     }
 }
 
-fn request<F>(ctx: &ModeContext, f: F)
+fn request<F>(ctx: &ModeContext, include_remotes: bool, f: F)
 where
     F: 'static + Send + Sync + FnOnce(&dyn Backend) -> BackendResult<()>,
 {
@@ -250,11 +502,7 @@ where
     thread::spawn(move || {
         use std::ops::Deref;
 
-        let mut result = f(ctx.backend.deref()).and_then(|_| ctx.backend.branches());
-        if let Ok(entries) = &mut result {
-            entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
-        }
-
+        let result = f(ctx.backend.deref()).and_then(|_| ctx.backend.branches(include_remotes));
         ctx.event_sender.send_response(ModeResponse::Branches(Response::Refresh(result)));
     });
 }
@@ -1,11 +1,30 @@
+use std::thread;
+
 use crate::{
+    backend::{BackendResult, DiffOptions, DiffStatEntry, RevisionEntry},
     mode::*,
-    platform::Key,
-    ui::{Drawer, RESERVED_LINES_COUNT},
+    platform::{Key, Platform},
+    ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
 };
 
+// diffs this big are offered to the pager instead of being drawn
+const LARGE_OUTPUT_LEN: usize = 8 * 1024 * 1024;
+
 pub enum Response {
     Refresh(String),
+    Stat(BackendResult<Vec<DiffStatEntry>>),
+}
+
+// what was diffed so it can be requested again with different options
+#[derive(Clone, Debug)]
+pub enum DiffRequest {
+    Changes(Vec<RevisionEntry>),
+    Staged,
+    Revision(String, usize, Vec<RevisionEntry>), // revision, parent count, entries
+    Stash(usize, Vec<RevisionEntry>),
+    StashWorkingTree(usize, Vec<RevisionEntry>), // the working tree against the stash
+    MergeBase(String),
+    Renames(Option<String>, Vec<RevisionEntry>),
 }
 
 #[derive(Clone, Debug)]
@@ -19,29 +38,331 @@ impl Default for State {
     }
 }
 
+impl SelectEntryDraw for DiffStatEntry {
+    fn draw(&self, drawer: &mut Drawer, hovered: bool, _: bool) -> usize {
+        let insertions = format!("+{}", self.insertions);
+        let deletions = format!("-{}", self.deletions);
+        if hovered {
+            drawer.fmt(format_args!("{:>6} {:>6} {}", insertions, deletions, self.path));
+        } else {
+            drawer.fmt(format_args!(
+                "{}{:>6} {}{:>6} {}{}",
+                Color::DarkGreen,
+                insertions,
+                Color::DarkRed,
+                deletions,
+                Color::White,
+                self.path
+            ));
+        }
+        1
+    }
+}
+
+// line index where each file starts in the diff, in the same order as the stat entries
+fn file_start_lines(diff: &str) -> impl Iterator<Item = usize> + '_ {
+    diff.lines()
+        .enumerate()
+        .filter(|(_, l)| l.starts_with("diff --git ") || l.starts_with("diff --cc ") || l.starts_with("diff --combined "))
+        .map(|(i, _)| i)
+}
+
+// git only diffs content as binary when it finds a NUL early on, so files in another encoding or
+// forced to diff as text can still fill the output with garbage that is replaced here
+fn collapse_binary_files(diff: String) -> String {
+    fn is_garbage(c: char) -> bool {
+        c == char::REPLACEMENT_CHARACTER || (c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    }
+
+    if !diff.contains(is_garbage) {
+        return diff;
+    }
+
+    let mut collapsed = String::with_capacity(diff.len());
+    let mut rest = &diff[..];
+    while !rest.is_empty() {
+        let end = match rest.find("\ndiff --") {
+            Some(i) => i + 1,
+            None => rest.len(),
+        };
+        let (file, next) = rest.split_at(end);
+        rest = next;
+
+        let hunks_start = match file.find("\n@@") {
+            Some(i) => i + 1,
+            None => file.len(),
+        };
+        let (header, hunks) = file.split_at(hunks_start);
+        let char_count = hunks.chars().count();
+        let garbage_count = hunks.chars().filter(|&c| is_garbage(c)).count();
+        // more than a tenth of it unreadable
+        if garbage_count * 10 > char_count {
+            for line in header.lines().filter(|l| !l.starts_with("--- ") && !l.starts_with("+++ ")) {
+                collapsed.push_str(line);
+                collapsed.push('\n');
+            }
+            collapsed.push_str("[binary file]\n");
+        } else {
+            collapsed.push_str(file);
+        }
+    }
+    collapsed
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Mode {
     state: State,
     output: Output,
     from: ModeKind,
+    request: Option<DiffRequest>,
+    options: DiffOptions,
+    parent: usize, // index into the revision parents, past the last one means the combined diff
+    left_help: String,
+    show_whitespace: bool,
+    search: ReadLine,
+    search_has_focus: bool,
+    search_matches: Vec<usize>, // line indices
+    file_lines: Vec<usize>,     // line indices where each file starts
+    current_match: usize,
+    stat: Option<Vec<DiffStatEntry>>, // computed when first asked for
+    show_stat: bool,
+    stat_select: SelectMenu,
+    count: Count,
+    large_output: Option<String>, // waiting for the user to choose the pager or not
+}
+impl Mode {
+    fn is_searching(&self) -> bool {
+        self.search_has_focus || !self.search.input().is_empty()
+    }
+
+    fn available_height(&self, viewport_size: (u16, u16)) -> usize {
+        let search_line_count = if self.is_searching() { 1 } else { 0 };
+        (viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT + search_line_count)
+    }
+
+    fn set_output(&mut self, output: String) {
+        self.output.set(output);
+        self.file_lines = file_start_lines(self.output.text()).collect();
+        self.find_search_matches();
+    }
+
+    fn find_search_matches(&mut self) {
+        let pattern = self.search.input();
+        self.search_matches.clear();
+        if !pattern.is_empty() {
+            let lines = self.output.text().lines().enumerate();
+            self.search_matches.extend(lines.filter(|(_, l)| l.contains(pattern)).map(|(i, _)| i));
+        }
+        self.current_match = self.current_match.min(self.search_matches.len().saturating_sub(1));
+    }
+
+    fn scroll_to_current_match(&mut self, available_height: usize) {
+        if let Some(&line) = self.search_matches.get(self.current_match) {
+            self.output.scroll_to(line, available_height);
+        }
+    }
+
+    fn request(&mut self, ctx: &ModeContext) {
+        let request = match &self.request {
+            Some(request) => request.clone(),
+            None => return,
+        };
+        self.state = State::Waiting;
+
+        self.left_help.clear();
+        self.left_help.push_str(if self.options.ignore_whitespace { "[w]show whitespace" } else { "[w]ignore whitespace" });
+        self.left_help.push_str(&format!(" [+/-]context {}", self.options.context_lines));
+        if let DiffRequest::Revision(_, parent_count, _) = request {
+            if parent_count > 1 {
+                let parent = if self.parent < parent_count {
+                    format!(" [p]parent {}/{}", self.parent + 1, parent_count)
+                } else {
+                    " [p]combined".into()
+                };
+                self.left_help.push_str(&parent);
+            }
+        }
+
+        let ctx = ctx.clone();
+        let options = self.options;
+        let parent = self.parent;
+        thread::spawn(move || {
+            let result = match &request {
+                DiffRequest::Changes(entries) => ctx.backend.diff(None, entries, &options),
+                DiffRequest::Staged => ctx.backend.diff_staged(&options),
+                DiffRequest::Revision(revision, _, entries) if parent == 0 => {
+                    ctx.backend.diff(Some(revision), entries, &options)
+                }
+                DiffRequest::Revision(revision, parent_count, entries) => {
+                    // parents are numbered from 1 in 'rev^n'
+                    let parent = if parent < *parent_count { Some(parent + 1) } else { None };
+                    ctx.backend.diff_parent(revision, parent, entries, &options)
+                }
+                DiffRequest::Stash(id, entries) => ctx.backend.stash_diff(*id, entries, &options),
+                DiffRequest::StashWorkingTree(id, entries) => ctx.backend.diff_stash_working_tree(*id, entries, &options),
+                DiffRequest::MergeBase(branch) => ctx.backend.diff_merge_base(branch, &options),
+                DiffRequest::Renames(revision, entries) => ctx.backend.diff_renames(revision.as_deref(), entries, &options),
+            };
+            let output = match result {
+                Ok(output) => collapse_binary_files(output),
+                Err(error) => error,
+            };
+            ctx.event_sender.send_response(ModeResponse::Diff(Response::Refresh(output)));
+        });
+    }
 }
 
 impl ModeTrait for Mode {
-    fn on_enter(&mut self, _ctx: &ModeContext, info: ModeChangeInfo) {
+    fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo) {
         if let State::Waiting = self.state {
             return;
         }
-        self.state = State::Waiting;
         self.from = info.from;
+        self.request = info.info.and_then(|info| as_variant!(info, ModeInfo::Diff));
+        self.parent = 0;
+        let toggles = ctx.toggles.lock().unwrap().clone();
+        self.options.ignore_whitespace = toggles.ignore_whitespace;
+        self.show_whitespace = toggles.mark_whitespace;
         self.output.set(String::new());
+        self.request(ctx);
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if let Some(output) = self.large_output.take() {
+            if key.is_submit() || key == Key::Char('y') {
+                match Platform::suspend(|| Platform::run_pager(&output)) {
+                    // it was seen in the pager, so there is nothing left to show here
+                    Ok(()) => {
+                        ctx.event_sender.send_mode_revert();
+                        return ModeStatus { pending_input: true };
+                    }
+                    Err(error) => ctx.event_sender.send_notice(format!("could not run pager: {}", error)),
+                }
+            }
+            self.set_output(output);
+            return ModeStatus { pending_input: true };
+        }
+
+        if self.search_has_focus {
+            if key.is_submit() {
+                self.search_has_focus = false;
+                self.current_match = 0;
+                self.find_search_matches();
+                self.scroll_to_current_match(self.available_height(ctx.viewport_size));
+            } else if key.is_cancel() {
+                self.search_has_focus = false;
+                self.search.clear();
+                self.search_matches.clear();
+            } else {
+                self.search.on_key(key);
+            }
+            return ModeStatus { pending_input: true };
+        }
+
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
+
+        if self.show_stat {
+            let entries_len = self.stat.as_ref().map(Vec::len).unwrap_or(0);
+            let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT + 1);
+            self.stat_select.on_key(entries_len, available_height, count, key);
+            match key {
+                Key::Enter => {
+                    if let Some(&line) = self.file_lines.get(self.stat_select.cursor) {
+                        self.output.scroll_to(line, self.available_height(ctx.viewport_size));
+                    }
+                    self.show_stat = false;
+                }
+                Key::Tab => self.show_stat = false,
+                _ if key.is_cancel() => {
+                    self.show_stat = false;
+                    return ModeStatus { pending_input: true };
+                }
+                _ => (),
+            }
+            return ModeStatus { pending_input: false };
+        }
+
         match self.state {
             State::Idle => {
+                let available_height = self.available_height(ctx.viewport_size);
                 if self.output.line_count() > 1 {
-                    let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
-                    self.output.on_key(available_height, key);
+                    self.output.on_key(available_height, count, key);
+                }
+                match key {
+                    Key::Char('/') => {
+                        self.search_has_focus = true;
+                        self.search.clear();
+                        self.search_matches.clear();
+                    }
+                    Key::Char('n') if !self.search_matches.is_empty() => {
+                        self.current_match = (self.current_match + 1) % self.search_matches.len();
+                        self.scroll_to_current_match(available_height);
+                    }
+                    Key::Char('N') if !self.search_matches.is_empty() => {
+                        let len = self.search_matches.len();
+                        self.current_match = (self.current_match + len - 1) % len;
+                        self.scroll_to_current_match(available_height);
+                    }
+                    Key::Char(']') => {
+                        let scroll = self.output.scroll();
+                        if let Some(&line) = self.file_lines.iter().find(|&&l| l > scroll) {
+                            self.output.scroll_to(line, available_height);
+                        }
+                    }
+                    Key::Char('[') => {
+                        let scroll = self.output.scroll();
+                        if let Some(&line) = self.file_lines.iter().rev().find(|&&l| l < scroll) {
+                            self.output.scroll_to(line, available_height);
+                        }
+                    }
+                    Key::Char('w') => {
+                        self.options.ignore_whitespace = !self.options.ignore_whitespace;
+                        ctx.toggles.lock().unwrap().ignore_whitespace = self.options.ignore_whitespace;
+                        self.request(ctx);
+                    }
+                    Key::Char('+') => {
+                        self.options.context_lines += 1;
+                        self.request(ctx);
+                    }
+                    Key::Char('-') if self.options.context_lines > 0 => {
+                        self.options.context_lines -= 1;
+                        self.request(ctx);
+                    }
+                    Key::Char('W') => {
+                        self.show_whitespace = !self.show_whitespace;
+                        ctx.toggles.lock().unwrap().mark_whitespace = self.show_whitespace;
+                    }
+                    Key::Tab if self.stat.is_some() => {
+                        self.show_stat = true;
+                        self.stat_select = SelectMenu::default();
+                    }
+                    Key::Tab => {
+                        self.state = State::Waiting;
+                        let ctx = ctx.clone();
+                        let diff = self.output.text().to_string();
+                        thread::spawn(move || {
+                            let result = ctx.backend.diff_stat(&diff);
+                            ctx.event_sender.send_response(ModeResponse::Diff(Response::Stat(result)));
+                        });
+                    }
+                    Key::Char('y') => {
+                        ctx.event_sender.send_notice(match Platform::set_clipboard(self.output.text()) {
+                            Ok(()) => "copied to clipboard".into(),
+                            Err(error) => error,
+                        });
+                    }
+                    Key::Char('p') => {
+                        if let Some(DiffRequest::Revision(_, parent_count, _)) = self.request {
+                            if parent_count > 1 {
+                                self.parent = (self.parent + 1) % (parent_count + 1);
+                                self.request(ctx);
+                            }
+                        }
+                    }
+                    _ => (),
                 }
             }
             _ => (),
@@ -50,7 +371,7 @@ impl ModeTrait for Mode {
         ModeStatus { pending_input: false }
     }
 
-    fn on_response(&mut self, _ctx: &ModeContext, response: ModeResponse) {
+    fn on_response(&mut self, ctx: &ModeContext, response: ModeResponse) {
         let response = as_variant!(response, ModeResponse::Diff).unwrap();
         match response {
             Response::Refresh(info) => {
@@ -58,7 +379,25 @@ impl ModeTrait for Mode {
                     self.state = State::Idle;
                 }
                 if let State::Idle = self.state {
-                    self.output.set(info);
+                    if info.len() > LARGE_OUTPUT_LEN {
+                        self.set_output(String::new());
+                        self.large_output = Some(info);
+                    } else {
+                        self.set_output(info);
+                    }
+                    self.stat = None;
+                    self.show_stat = false;
+                }
+            }
+            Response::Stat(result) => {
+                self.state = State::Idle;
+                match result {
+                    Ok(entries) => {
+                        self.stat = Some(entries);
+                        self.show_stat = true;
+                        self.stat_select = SelectMenu::default();
+                    }
+                    Err(error) => ctx.event_sender.send_notice(error.lines().last().unwrap_or("").into()),
                 }
             }
         }
@@ -71,12 +410,134 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = self.available_height(size);
+        self.output.on_resize(available_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
-        ("details", "", "[Left]back [arrows]move")
+        if self.show_stat {
+            return ("files", "[enter]jump to file", "[tab]back to diff [arrows]move");
+        }
+        (
+            "details",
+            &self.left_help,
+            "[tab]files [[/]]next/previous file [/]search [n/N]next/previous match [W]mark whitespace [y]copy [Left]back [arrows]move",
+        )
     }
 
     fn draw(&self, drawer: &mut Drawer) {
+        if let Some(output) = &self.large_output {
+            drawer.fmt(format_args!("output is large ({} MB), open in pager? [y]yes [n]no", output.len() / (1024 * 1024)));
+            return;
+        }
+
+        if let (true, Some(entries)) = (self.show_stat, &self.stat) {
+            let insertions: usize = entries.iter().map(|e| e.insertions).sum();
+            let deletions: usize = entries.iter().map(|e| e.deletions).sum();
+            drawer.fmt(format_args!(
+                "{} file(s) changed, {} insertion(s), {} deletion(s)",
+                entries.len(),
+                insertions,
+                deletions
+            ));
+            drawer.next_line();
+            drawer.select_menu(&self.stat_select, 1, false, entries.iter());
+            return;
+        }
+
         //log(format!("start to draw diff: \n"));
-        drawer.diff(&self.output);
+        if self.is_searching() {
+            drawer.search(&self.search, self.search_has_focus, (self.current_match, self.search_matches.len()));
+        }
+        drawer.diff(&self.output, self.show_whitespace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+
+    #[test]
+    fn search_counts_and_cycles_matches() {
+        let (ctx, _receiver) = ModeContext::for_test(Arc::new(FakeBackend::default()));
+        let mut mode = Mode::default();
+        let text: Vec<_> =
+            (0..100).map(|i| if i % 30 == 0 { format!("+match {}", i) } else { format!(" line {}", i) }).collect();
+        mode.on_response(&ctx, ModeResponse::Diff(Response::Refresh(text.join("\n"))));
+
+        mode.on_key(&ctx, Key::Char('/'));
+        for key in [Key::Char('m'), Key::Char('a'), Key::Char('t'), Key::Char('c'), Key::Char('h')] {
+            assert!(mode.on_key(&ctx, key).pending_input);
+        }
+        mode.on_key(&ctx, Key::Enter);
+        assert_eq!(mode.search_matches, [0, 30, 60, 90]);
+        assert_eq!(mode.current_match, 0);
+
+        mode.on_key(&ctx, Key::Char('n'));
+        assert_eq!(mode.current_match, 1);
+        assert_eq!(mode.output.lines_from_scroll().next(), Some("+match 30"));
+
+        mode.on_key(&ctx, Key::Char('N'));
+        mode.on_key(&ctx, Key::Char('N'));
+        assert_eq!(mode.current_match, 3);
+    }
+
+    #[test]
+    fn stat_entry_jumps_to_its_file() {
+        let (ctx, _receiver) = ModeContext::for_test(Arc::new(FakeBackend::default()));
+        let mut mode = Mode::default();
+        let mut text = vec!["diff --git a/a b/a".to_string()];
+        text.extend((0..50).map(|i| format!("+line {}", i)));
+        text.push("diff --git a/b b/b".into());
+        text.extend((0..50).map(|i| format!("-line {}", i)));
+        mode.on_response(&ctx, ModeResponse::Diff(Response::Refresh(text.join("\n"))));
+
+        let entries = ["a", "b"].iter().map(|p| DiffStatEntry { path: p.to_string(), insertions: 50, deletions: 0 });
+        mode.on_response(&ctx, ModeResponse::Diff(Response::Stat(Ok(entries.collect()))));
+        assert!(mode.show_stat);
+
+        mode.on_key(&ctx, Key::Down);
+        mode.on_key(&ctx, Key::Enter);
+        assert!(!mode.show_stat);
+        assert_eq!(mode.output.lines_from_scroll().next(), Some("diff --git a/b b/b"));
+    }
+
+    #[test]
+    fn large_output_waits_for_pager_choice() {
+        let (ctx, _receiver) = ModeContext::for_test(Arc::new(FakeBackend::default()));
+        let mut mode = Mode::default();
+        let text = "+line\n".repeat(LARGE_OUTPUT_LEN / 6 + 1);
+        mode.on_response(&ctx, ModeResponse::Diff(Response::Refresh(text)));
+        assert!(mode.large_output.is_some());
+        assert!(mode.output.text().is_empty());
+
+        assert!(mode.on_key(&ctx, Key::Char('n')).pending_input);
+        assert!(mode.large_output.is_none());
+        assert_eq!(mode.output.lines_from_scroll().next(), Some("+line"));
+    }
+
+    #[test]
+    fn brackets_jump_between_files() {
+        let (ctx, _receiver) = ModeContext::for_test(Arc::new(FakeBackend::default()));
+        let mut mode = Mode::default();
+        let mut text = Vec::new();
+        for path in ["a", "b", "c"] {
+            text.push(format!("diff --git a/{} b/{}", path, path));
+            text.extend((0..50).map(|i| format!("+line {}", i)));
+        }
+        mode.on_response(&ctx, ModeResponse::Diff(Response::Refresh(text.join("\n"))));
+        assert_eq!(mode.file_lines, [0, 51, 102]);
+
+        mode.on_key(&ctx, Key::Char(']'));
+        assert_eq!(mode.output.lines_from_scroll().next(), Some("diff --git a/b b/b"));
+        mode.on_key(&ctx, Key::Down);
+        mode.on_key(&ctx, Key::Char('['));
+        assert_eq!(mode.output.lines_from_scroll().next(), Some("diff --git a/b b/b"));
+        mode.on_key(&ctx, Key::Char('['));
+        assert_eq!(mode.output.scroll(), 0);
     }
 }
@@ -0,0 +1,192 @@
+use std::thread;
+
+use crate::{
+    backend::{BackendResult, BlameEntry},
+    mode::*,
+    platform::Key,
+    ui::{Drawer, RESERVED_LINES_COUNT},
+};
+
+pub enum Response {
+    Refresh(BackendResult<String>),
+    Blame(BackendResult<Vec<BlameEntry>>),
+}
+
+#[derive(Default, Clone, Debug)]
+enum State {
+    #[default]
+    Idle,
+    Waiting,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Mode {
+    state: State,
+    output: Output,
+    count: Count,
+    title: String,
+    revision: String,
+    path: String,
+    from: ModeKind,
+    has_conflicts: bool,
+    text: String,
+    blame: Option<String>, // kept so toggling it back on does not run git again
+    show_blame: bool,
+}
+impl Mode {
+    // ':2' and ':3' are the ours and theirs sides of a conflicted file in the index
+    fn conflict_side(&self) -> Option<&'static str> {
+        match &self.revision[..] {
+            ":2" => Some("ours"),
+            ":3" => Some("theirs"),
+            _ => None,
+        }
+    }
+
+    fn request(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting;
+        self.output.set(String::new());
+        self.blame = None;
+        self.show_blame = false;
+        // no revision means the working tree
+        self.title = match (&self.revision[..], self.conflict_side()) {
+            ("", _) => self.path.clone(),
+            (_, Some(side)) => format!("{} ({})", self.path, side),
+            (revision, None) => format!("{} @ {}", self.path, revision),
+        };
+
+        let revision = self.revision.clone();
+        let path = self.path.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let result = match &revision[..] {
+                "" => ctx.backend.working_file(&path),
+                _ => ctx.backend.show_file(&revision, &path),
+            };
+            ctx.event_sender.send_response(ModeResponse::FileView(Response::Refresh(result)));
+        });
+    }
+}
+
+impl ModeTrait for Mode {
+    fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo) {
+        if let State::Waiting = self.state {
+            return;
+        }
+        self.from = info.from;
+        let (revision, path) = as_variant!(info.info.unwrap(), ModeInfo::FileView).unwrap();
+        self.revision = revision;
+        self.path = path;
+        self.request(ctx);
+    }
+
+    fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
+        if let State::Idle = self.state {
+            let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+            self.output.on_key(available_height, count, key);
+
+            if let (Key::Tab, Some(side)) = (key, self.conflict_side()) {
+                self.revision = if side == "ours" { ":3".into() } else { ":2".into() };
+                self.request(ctx);
+            } else if let (Key::Char('a'), None) = (key, self.conflict_side()) {
+                self.show_blame = !self.show_blame;
+                if !self.show_blame {
+                    self.output.set(self.text.clone());
+                } else if let Some(blame) = &self.blame {
+                    self.output.set(blame.clone());
+                } else {
+                    self.state = State::Waiting;
+
+                    let revision = self.revision.clone();
+                    let path = self.path.clone();
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let result = ctx.backend.blame(&revision, &path);
+                        ctx.event_sender.send_response(ModeResponse::FileView(Response::Blame(result)));
+                    });
+                }
+            }
+        }
+
+        ModeStatus { pending_input: false }
+    }
+
+    fn on_response(&mut self, _ctx: &ModeContext, response: ModeResponse) {
+        let response = as_variant!(response, ModeResponse::FileView).unwrap();
+        match response {
+            Response::Refresh(result) => {
+                if let State::Waiting = self.state {
+                    self.state = State::Idle;
+                }
+                let output = match result {
+                    Ok(output) => output,
+                    Err(error) => error,
+                };
+                self.has_conflicts = output.lines().any(|l| l.starts_with("<<<<<<<"));
+                self.text = output.clone();
+                self.output.set(output);
+            }
+            Response::Blame(result) => {
+                if let State::Waiting = self.state {
+                    self.state = State::Idle;
+                }
+                let blame = match result {
+                    Ok(entries) => format_blame(&entries),
+                    Err(error) => error,
+                };
+                self.blame = Some(blame.clone());
+                if self.show_blame {
+                    self.output.set(blame);
+                }
+            }
+        }
+    }
+
+    fn is_waiting_response(&self) -> bool {
+        match self.state {
+            State::Idle => false,
+            State::Waiting => true,
+        }
+    }
+
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.output.on_resize(available_height);
+    }
+
+    fn header(&self) -> (&str, &str, &str) {
+        let name = if self.show_blame { "file annotated" } else { "file" };
+        let right_help = if self.conflict_side().is_some() {
+            "[tab]ours/theirs [Left]back [arrows]move"
+        } else if self.has_conflicts && !self.show_blame {
+            "ours in green, base in yellow, theirs in blue [a]annotate [Left]back [arrows]move"
+        } else {
+            "[a]annotate [Left]back [arrows]move"
+        };
+        (name, &self.title, right_help)
+    }
+
+    fn draw(&self, drawer: &mut Drawer) {
+        if self.has_conflicts && !self.show_blame {
+            drawer.conflicts(&self.output);
+        } else {
+            drawer.output(&self.output);
+        }
+    }
+}
+
+// short hash and author in front of every line
+fn format_blame(entries: &[BlameEntry]) -> String {
+    const AUTHOR_WIDTH: usize = 16;
+
+    let mut text = String::new();
+    for entry in entries {
+        let author: String = entry.author.chars().take(AUTHOR_WIDTH).collect();
+        text.push_str(&format!("{} {:<width$} | {}\n", entry.hash, author, entry.line, width = AUTHOR_WIDTH));
+    }
+    text
+}
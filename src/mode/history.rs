@@ -0,0 +1,102 @@
+use crate::{
+    backend::{command_history, CommandEntry},
+    mode::*,
+    platform::Key,
+    ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
+};
+
+impl SelectEntryDraw for CommandEntry {
+    fn draw(&self, drawer: &mut Drawer, hovered: bool, _: bool) -> usize {
+        let (status_color, status) = if self.success { (Color::DarkGreen, "ok") } else { (Color::DarkRed, "failed") };
+        let status_color = if hovered { Color::White } else { status_color };
+
+        drawer.fmt(format_args!("{}[{:>6}] {}{}", status_color, status, Color::White, &self.command));
+        1
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Mode {
+    entries: Vec<CommandEntry>,
+    output: Output,
+    select: SelectMenu,
+    count: Count,
+    filter: Filter,
+}
+
+impl ModeTrait for Mode {
+    fn on_enter(&mut self, _ctx: &ModeContext, _info: ModeChangeInfo) {
+        self.entries = command_history();
+        self.entries.reverse(); // most recent first
+        self.output.set(String::new());
+
+        self.filter.filter(self.entries.iter());
+        self.select.saturate_cursor(self.filter.visible_indices().len());
+    }
+
+    fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if self.filter.has_focus() {
+            self.filter.on_key(key);
+            self.filter.filter(self.entries.iter());
+            self.select.saturate_cursor(self.filter.visible_indices().len());
+
+            return ModeStatus { pending_input: true };
+        }
+
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
+        let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        if self.output.text().is_empty() {
+            self.select.on_key(self.filter.visible_indices().len(), available_height, count, key);
+        } else {
+            self.output.on_key(available_height, count, key);
+        }
+
+        match key {
+            Key::Ctrl('f') => self.filter.enter(),
+            // toggles between the command list and what the last operation printed
+            Key::Char('o') if !self.output.text().is_empty() => self.output.set(String::new()),
+            Key::Char('o') => {
+                let output = ctx.last_output.lock().unwrap().clone();
+                self.output.set(if output.is_empty() { "no operation output yet".into() } else { output });
+            }
+            _ => (),
+        }
+
+        ModeStatus { pending_input: false }
+    }
+
+    fn on_response(&mut self, _ctx: &ModeContext, _response: ModeResponse) {}
+
+    fn is_waiting_response(&self) -> bool {
+        false
+    }
+
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.select.on_resize(self.filter.visible_indices().len(), available_height);
+        self.output.on_resize(available_height);
+    }
+
+    fn header(&self) -> (&str, &str, &str) {
+        ("command history", "[o]last operation output", "[Left]back [arrows]move [ctrl+f]filter")
+    }
+
+    fn draw(&self, drawer: &mut Drawer) {
+        let filter_line_count = drawer.filter(&self.filter);
+        if !self.output.text().is_empty() {
+            drawer.output(&self.output);
+        } else if self.entries.is_empty() {
+            drawer.fmt(format_args!("{}no commands were run yet!", Color::DarkYellow));
+        } else {
+            drawer.select_menu(
+                &self.select,
+                filter_line_count,
+                false,
+                self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
+            );
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::thread;
+
+use crate::{
+    backend::{BackendResult, DiffOptions, RevisionEntry},
+    mode::*,
+    platform::Key,
+    ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
+};
+
+pub enum Response {
+    Refresh(BackendResult<String>),
+    Commit(String),
+    Committed(BackendResult<()>),
+}
+
+#[derive(Clone, Debug)]
+enum WaitOperation {
+    Refresh,
+    Stage,
+    Commit,
+}
+
+#[derive(Default, Clone, Debug)]
+enum State {
+    #[default]
+    Idle,
+    Waiting(WaitOperation),
+}
+
+#[derive(Clone, Debug)]
+pub struct Hunk {
+    path: String,
+    file_header: String, // 'diff --git' up to the '+++' line, needed to apply the hunk
+    text: String,        // starting with the '@@' line
+    selected: bool,
+}
+
+impl SelectEntryDraw for Hunk {
+    fn draw(&self, drawer: &mut Drawer, hovered: bool, full: bool) -> usize {
+        let selected_text = if self.selected { '+' } else { ' ' };
+        let header = self.text.lines().next().unwrap_or("");
+        if hovered {
+            drawer.fmt(format_args!("{} {} {}", selected_text, self.path, header));
+        } else {
+            drawer.fmt(format_args!("{} {} {}{}{}", selected_text, self.path, Color::DarkBlue, header, Color::White));
+        }
+
+        if !full {
+            return 1;
+        }
+
+        let mut line_count = 1;
+        for line in self.text.lines().skip(1) {
+            drawer.next_line();
+            drawer.str(line);
+            line_count += 1;
+        }
+        line_count
+    }
+}
+
+// splits a diff into its hunks, skipping files without any like binary ones
+pub fn parse_hunks(diff: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut path = String::new();
+    let mut file_header = String::new();
+    let mut in_file_header = false;
+
+    for line in diff.lines() {
+        if line.starts_with("diff ") {
+            in_file_header = true;
+            file_header.clear();
+            path = match line.rsplit_once(" b/") {
+                Some((_, path)) => path.into(),
+                None => line.into(),
+            };
+        } else if line.starts_with("@@") {
+            in_file_header = false;
+            hunks.push(Hunk { path: path.clone(), file_header: file_header.clone(), text: String::new(), selected: false });
+        } else if let Some(path_line) = line.strip_prefix("+++ ").filter(|_| in_file_header) {
+            if let Some(new_path) = path_line.strip_prefix("b/") {
+                path = new_path.into();
+            }
+        }
+
+        let text = match hunks.last_mut() {
+            Some(hunk) if !in_file_header => &mut hunk.text,
+            _ => &mut file_header,
+        };
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    hunks
+}
+
+// a patch with only the selected hunks, each file header written once
+fn selected_patch(hunks: &[Hunk]) -> String {
+    let mut patch = String::new();
+    let mut last_file_header = None;
+    for hunk in hunks.iter().filter(|h| h.selected) {
+        if last_file_header != Some(&hunk.file_header) {
+            patch.push_str(&hunk.file_header);
+            last_file_header = Some(&hunk.file_header);
+        }
+        patch.push_str(&hunk.text);
+    }
+    patch
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Mode {
+    state: State,
+    entries: Vec<RevisionEntry>,
+    hunks: Vec<Hunk>,
+    output: Output,
+    select: SelectMenu,
+    count: Count,
+    show_full_hovered_hunk: bool,
+}
+impl Mode {
+    fn request_hunks(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Refresh);
+        let entries = self.entries.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let result = ctx.backend.diff(None, &entries, &DiffOptions::default());
+            ctx.event_sender.send_response(ModeResponse::Hunks(Response::Refresh(result)));
+        });
+    }
+}
+
+impl ModeTrait for Mode {
+    fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo) {
+        if let State::Waiting(_) = self.state {
+            return;
+        }
+        if let Some(ModeInfo::Hunks(entries)) = info.info {
+            self.entries = entries;
+        }
+        self.output.set(String::new());
+        self.request_hunks(ctx);
+    }
+
+    fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
+        let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        if !self.output.text().is_empty() {
+            self.output.on_key(available_height, count, key);
+            return ModeStatus { pending_input: false };
+        }
+
+        match self.select.on_key(self.hunks.len(), available_height, count, key) {
+            SelectMenuAction::None => (),
+            SelectMenuAction::Toggle(i) => self.hunks[i].selected = !self.hunks[i].selected,
+            SelectMenuAction::ToggleAll => {
+                let all_selected = self.hunks.iter().all(|h| h.selected);
+                for hunk in &mut self.hunks {
+                    hunk.selected = !all_selected;
+                }
+            }
+        }
+
+        let any_selected = self.hunks.iter().any(|h| h.selected);
+        match key {
+            Key::Tab => self.show_full_hovered_hunk = !self.show_full_hovered_hunk,
+            Key::Char('i') if matches!(self.state, State::Idle) && any_selected => {
+                self.state = State::Waiting(WaitOperation::Stage);
+                let patch = selected_patch(&self.hunks);
+                let entries = self.entries.clone();
+                let ctx = ctx.clone();
+                thread::spawn(move || {
+                    let result = ctx
+                        .backend
+                        .stage_patch(&patch)
+                        .and_then(|_| ctx.backend.diff(None, &entries, &DiffOptions::default()));
+                    ctx.event_sender.send_response(ModeResponse::Hunks(Response::Refresh(result)));
+                });
+            }
+            Key::Char('c') if matches!(self.state, State::Idle) && any_selected => {
+                let not_empty = true;
+                let placeholder = "type in the commit message for the selected hunks...";
+                let on_submit = |ctx: &ModeContext, message: String| {
+                    ctx.event_sender.send_response(ModeResponse::Hunks(Response::Commit(message)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Hunks, not_empty, placeholder, on_submit).commit_message(),
+                );
+            }
+            _ => (),
+        }
+
+        ModeStatus { pending_input: false }
+    }
+
+    fn on_response(&mut self, ctx: &ModeContext, response: ModeResponse) {
+        let response = as_variant!(response, ModeResponse::Hunks).unwrap();
+        match response {
+            Response::Refresh(result) => {
+                self.state = State::Idle;
+                self.hunks.clear();
+                match result {
+                    Ok(diff) => {
+                        self.hunks = parse_hunks(&diff);
+                        if self.hunks.is_empty() {
+                            self.output.set("no hunks left".into());
+                        }
+                    }
+                    Err(error) => self.output.set(error),
+                }
+                self.select.saturate_cursor(self.hunks.len());
+            }
+            Response::Commit(message) => {
+                self.state = State::Waiting(WaitOperation::Commit);
+                let patch = selected_patch(&self.hunks);
+                let ctx = ctx.clone();
+                thread::spawn(move || {
+                    // only what was just staged gets committed, unselected hunks stay in the working tree,
+                    // so anything staged before would end up in the commit too
+                    let result = ctx
+                        .backend
+                        .has_staged_changes()
+                        .and_then(|staged| match staged {
+                            true => Err("there are staged changes already, commit or unstage them first".into()),
+                            false => ctx.backend.stage_patch(&patch),
+                        })
+                        .and_then(|_| ctx.backend.commit(&message, None, false, None));
+                    ctx.event_sender.send_response(ModeResponse::Hunks(Response::Committed(result)));
+                });
+            }
+            Response::Committed(result) => {
+                self.state = State::Idle;
+                match result {
+                    Ok(()) => ctx.event_sender.send_mode_change(ModeKind::Status, ModeChangeInfo::new(ModeKind::Hunks)),
+                    Err(error) => self.output.set(error),
+                }
+            }
+        }
+    }
+
+    fn is_waiting_response(&self) -> bool {
+        match self.state {
+            State::Idle => false,
+            State::Waiting(_) => true,
+        }
+    }
+
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.select.on_resize(self.hunks.len(), available_height);
+        self.output.on_resize(available_height);
+    }
+
+    fn header(&self) -> (&str, &str, &str) {
+        let name = match self.state {
+            State::Idle | State::Waiting(WaitOperation::Refresh) => "hunks",
+            State::Waiting(WaitOperation::Stage) => "stage hunks",
+            State::Waiting(WaitOperation::Commit) => "commit hunks",
+        };
+        let (left_help, right_help) =
+            ("[c]commit selected [i]stage selected", "[tab]full hunk [space]toggle [a]toggle all [Left]back [arrows]move");
+        (name, left_help, right_help)
+    }
+
+    fn draw(&self, drawer: &mut Drawer) {
+        if self.output.text().is_empty() {
+            drawer.select_menu(&self.select, 0, self.show_full_hovered_hunk, self.hunks.iter());
+        } else {
+            drawer.output(&self.output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/a b/a
+index 1111111..2222222 100644
+--- a/a
++++ b/a
+@@ -1,3 +1,4 @@
+ one
++two
+ three
+@@ -10,3 +11,3 @@
+ ten
+-eleven
++eleven!
+diff --git a/b b/b
+index 3333333..4444444 100644
+--- a/b
++++ b/b
+@@ -1 +1 @@
+-x
++y
+";
+
+    #[test]
+    fn parses_hunks_with_their_file_headers() {
+        let hunks = parse_hunks(DIFF);
+        let paths: Vec<_> = hunks.iter().map(|h| &h.path[..]).collect();
+        assert_eq!(paths, ["a", "a", "b"]);
+        assert!(hunks[0].file_header.starts_with("diff --git a/a b/a\n"));
+        assert!(hunks[0].file_header.ends_with("+++ b/a\n"));
+        assert_eq!(hunks[1].text, "@@ -10,3 +11,3 @@\n ten\n-eleven\n+eleven!\n");
+    }
+
+    #[test]
+    fn patch_only_has_selected_hunks() {
+        let mut hunks = parse_hunks(DIFF);
+        hunks[1].selected = true;
+        hunks[2].selected = true;
+
+        let patch = selected_patch(&hunks);
+        assert_eq!(patch.matches("diff --git").count(), 2);
+        assert!(!patch.contains("+two"));
+        assert!(patch.contains("+eleven!"));
+        assert!(patch.ends_with("-x\n+y\n"));
+    }
+}
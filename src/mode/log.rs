@@ -1,13 +1,24 @@
 use crate::{
     backend::{Backend, BackendResult, LogEntry},
     mode::*,
-    platform::Key,
+    platform::{Key, Platform},
     ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
 };
 use std::thread;
 
+const DEFAULT_BODY_PREVIEW_LINE_COUNT: usize = 6;
+const MIN_LIST_LINE_COUNT: usize = 2;
+
 pub enum Response {
     Refresh(BackendResult<(usize, Vec<LogEntry>)>),
+    RewordMessage(String, BackendResult<String>),
+    Reword(String),
+    PushPreview(String),
+    Pickaxe(String, bool),
+    ResetPreview(String, BackendResult<Vec<LogEntry>>), // revision, commits it would drop
+    Detached(bool),
+    Pruned(Vec<String>),
+    NewBranch(String),
 }
 
 #[derive(Clone, Debug)]
@@ -18,7 +29,12 @@ enum WaitOperation {
     Fetch,
     Pull,
     Push,
+    PushPreview,
     Reset,
+    Reword,
+    Gc,
+    Undo,
+    NewBranch,
 }
 
 #[derive(Clone, Debug)]
@@ -32,7 +48,31 @@ impl Default for State {
     }
 }
 
-impl SelectEntryDraw for LogEntry {
+// which commits are listed
+#[derive(Default, Clone, Debug)]
+struct LogQuery {
+    all: bool, // every ref instead of just HEAD
+    no_merges: bool,
+    first_parent: bool,
+    pickaxe: Option<(String, bool)>, // (term, regex) only commits whose diff adds or removes it
+}
+impl LogQuery {
+    fn entries(&self, backend: &dyn Backend, start: usize, len: usize) -> BackendResult<(usize, Vec<LogEntry>)> {
+        match &self.pickaxe {
+            Some((term, regex)) => backend.log_pickaxe(start, len, term, *regex),
+            None => backend.log(start, len, self.all, self.no_merges, self.first_parent),
+        }
+    }
+}
+
+// a log entry as drawn, optionally with the author email and the full hash
+struct LogEntryView<'a> {
+    entry: &'a LogEntry,
+    show_email: bool,
+    show_full_hash: bool,
+}
+
+impl SelectEntryDraw for LogEntryView<'_> {
     fn draw(&self, drawer: &mut Drawer, hovered: bool, full: bool) -> usize {
         fn color(color: Color, hovered: bool) -> Color {
             if hovered {
@@ -42,28 +82,48 @@ impl SelectEntryDraw for LogEntry {
             }
         }
 
+        let entry = self.entry;
+        let hash = if self.show_full_hash { &entry.full_hash } else { &entry.hash };
+        if entry.is_head() && !hovered {
+            drawer.background(Color::DarkGray);
+        }
+
         const MAX_AUTHOR_CHAR_COUNT: usize = 18;
-        let author = match self.author.char_indices().nth(MAX_AUTHOR_CHAR_COUNT) {
-            Some((i, _)) => &self.author[..i],
-            None => &self.author,
+        let author_with_email;
+        let author = if self.show_email && !entry.email.is_empty() {
+            author_with_email = format!("{} <{}>", entry.author, entry.email);
+            &author_with_email[..]
+        } else {
+            match entry.author.char_indices().nth(MAX_AUTHOR_CHAR_COUNT) {
+                Some((i, _)) => &entry.author[..i],
+                None => &entry.author,
+            }
         };
 
-        let mut total_chars = self.graph.chars().count()
+        let initials: String =
+            entry.author.split_whitespace().filter_map(|w| w.chars().next()).take(2).flat_map(char::to_uppercase).collect();
+
+        let mut total_chars = entry.graph.chars().count()
             + 1
-            + self.hash.chars().count()
+            + hash.chars().count()
             + 1
-            + self.date.chars().count()
+            + entry.date.chars().count()
+            + 1
+            + 2
             + 1
             + author.chars().count()
             + 1;
 
-        if !self.refs.is_empty() {
-            total_chars += self.refs.chars().count() + 3;
+        if !entry.refs.is_empty() {
+            total_chars += entry.refs.chars().count() + 3;
+        }
+        if !entry.source.is_empty() {
+            total_chars += entry.source.chars().count() + 3;
         }
 
         let (line_count, message) = if full {
             let mut line_count = 0;
-            for line in self.message.lines() {
+            for line in entry.message.lines() {
                 let mut x = 0;
                 for _ in line.chars() {
                     if x >= drawer.viewport_size.0 as _ {
@@ -74,10 +134,10 @@ impl SelectEntryDraw for LogEntry {
 
                 line_count += 1;
             }
-            (line_count, &self.message[..])
+            (line_count, &entry.message[..])
         } else {
             let available_width = (drawer.viewport_size.0 as usize).saturating_sub(total_chars);
-            let message = self.message.lines().next().unwrap_or("");
+            let message = entry.message.lines().next().unwrap_or("");
             let message = match message.char_indices().nth(available_width) {
                 Some((i, _)) => &message[..i],
                 None => &message,
@@ -85,25 +145,35 @@ impl SelectEntryDraw for LogEntry {
             (0, message)
         };
 
-        let (refs_begin, refs_end) = match &self.refs[..] {
+        let (refs_begin, refs_end) = match &entry.refs[..] {
             "" => ("", ""),
             _ => ("(", ") "),
         };
+        let (source_begin, source_end) = match &entry.source[..] {
+            "" => ("", ""),
+            _ => ("[", "] "),
+        };
 
         drawer.fmt(format_args!(
-            "{}{} {}{} {}{} {}{} {}{}{}{}{}",
+            "{}{} {}{} {}{} {}{:<2} {}{} {}{}{}{}{}{}{}{}{}",
             color(Color::White, hovered),
-            &self.graph,
+            &entry.graph,
             color(Color::DarkYellow, hovered),
-            &self.hash,
+            hash,
             color(Color::DarkBlue, hovered),
-            &self.date,
+            &entry.date,
+            color(Color::from_text(&entry.author), hovered),
+            initials,
             color(Color::DarkGreen, hovered),
             author,
             color(Color::DarkRed, hovered),
             refs_begin,
-            &self.refs,
+            &entry.refs,
             refs_end,
+            color(Color::DarkMagenta, hovered),
+            source_begin,
+            &entry.source,
+            source_end,
             color(Color::White, hovered),
         ));
 
@@ -130,22 +200,82 @@ pub struct Mode {
     entries: Vec<LogEntry>,
     output: Output,
     select: SelectMenu,
+    count: Count,
     filter: Filter,
     show_full_hovered_message: bool,
+    show_email: bool,
+    show_full_hash: bool,
+    show_body: bool,
+    body_preview_line_count: usize, // including the separator, resized with '<' and '>'
+    query: LogQuery,
+    undo: Option<String>,
+    pruned: Vec<String>,                            // remote branches the last fetch deleted
+    detached: bool,                                 // offers to create a branch so commits made from here are not lost
+    reword: Option<(String, String)>,               // (revision, original message)
+    confirm_reset: Option<(String, Vec<LogEntry>)>, // (revision, commits it would drop)
+}
+impl Mode {
+    fn preview_line_count(&self) -> usize {
+        if self.show_body {
+            self.body_preview_line_count
+        } else {
+            0
+        }
+    }
+
+    // an empty revision resets to the remote branch
+    fn reset(&mut self, ctx: &ModeContext, revision: String, confirmed: bool) {
+        self.state = State::Waiting(WaitOperation::Reset);
+        if confirmed || ctx.config.expert_mode {
+            request(ctx, self.query.clone(), move |b| b.reset(&revision));
+        } else {
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let result = ctx.backend.log_range(&revision, "HEAD");
+                ctx.event_sender.send_response(ModeResponse::Log(Response::ResetPreview(revision, result)));
+            });
+        }
+    }
+
+    // lines left for the entries, the body preview takes some when shown
+    fn list_height(&self, viewport_height: u16) -> usize {
+        let available_height = (viewport_height as usize).saturating_sub(RESERVED_LINES_COUNT);
+        available_height.saturating_sub(self.preview_line_count())
+    }
 }
+
 impl ModeTrait for Mode {
-    fn on_enter(&mut self, ctx: &ModeContext, _info: ModeChangeInfo) {
+    fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo) {
         if let State::Waiting(_) = self.state {
             return;
         }
         self.state = State::Waiting(WaitOperation::Refresh);
+        if let Some(ModeInfo::Undoable) = info.info {
+            self.undo = ctx.backend.undo_description();
+        }
+
+        // the mode is rebuilt on enter, so pick up the toggles shown on the status line
+        let toggles = ctx.toggles.lock().unwrap().clone();
+        self.query.all = toggles.all_refs;
+        self.query.no_merges = toggles.hide_merges;
+        self.query.first_parent = toggles.first_parent;
 
         self.output.set(String::new());
         self.filter.filter(self.entries.iter());
         self.select.saturate_cursor(self.filter.visible_indices().len());
         self.show_full_hovered_message = false;
 
-        request(ctx, |_| Ok(()));
+        request(ctx, self.query.clone(), |_| Ok(()));
+    }
+
+    fn is_showing_output(&self) -> bool {
+        !self.output.text().is_empty()
+    }
+
+    fn request_refresh(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Refresh);
+        // everything paged in so far, otherwise the list and the cursor jump back to the first page
+        request_entries(ctx, self.query.clone(), self.entries.len(), |_| Ok(()));
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
@@ -157,16 +287,24 @@ impl ModeTrait for Mode {
             return ModeStatus { pending_input: true };
         }
 
+        let confirm_reset = self.confirm_reset.take();
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
         let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
-        self.select.on_key(self.filter.visible_indices().len(), available_height, key);
+        self.select.on_key(self.filter.visible_indices().len(), self.list_height(ctx.viewport_size.1), count, key);
 
         let current_entry_index = self.filter.get_visible_index(self.select.cursor);
         if matches!(self.state, State::Idle) && current_entry_index.map(|i| i + 1 == self.entries.len()).unwrap_or(false) {
             self.state = State::Waiting(WaitOperation::Refresh);
             let start = self.entries.len();
+            let query = self.query.clone();
             let ctx = ctx.clone();
             thread::spawn(move || {
-                let result = ctx.backend.log(start, available_height);
+                use std::ops::Deref;
+
+                let result = query.entries(ctx.backend.deref(), start, available_height);
                 ctx.event_sender.send_response(ModeResponse::Log(Response::Refresh(result)));
             });
         }
@@ -179,53 +317,197 @@ impl ModeTrait for Mode {
             }
         } else if let Key::Tab = key {
             self.show_full_hovered_message = !self.show_full_hovered_message;
+        } else if let Key::Char('e') = key {
+            self.show_email = !self.show_email;
+        } else if let Key::Char('h') = key {
+            self.show_full_hash = !self.show_full_hash;
+        } else if let Key::Char('B') = key {
+            self.show_body = !self.show_body;
+            if self.body_preview_line_count == 0 {
+                self.body_preview_line_count = DEFAULT_BODY_PREVIEW_LINE_COUNT;
+            }
+            let list_height = self.list_height(ctx.viewport_size.1);
+            self.select.on_resize(self.filter.visible_indices().len(), list_height);
+        } else if matches!(key, Key::Char('<') | Key::Char('>')) && self.show_body {
+            // favors the list or the preview, always keeping a few lines for both
+            let max_line_count = available_height.saturating_sub(MIN_LIST_LINE_COUNT).max(2);
+            self.body_preview_line_count = match key {
+                Key::Char('>') => self.body_preview_line_count + 1,
+                _ => self.body_preview_line_count - 1,
+            }
+            .clamp(2, max_line_count);
+            let list_height = self.list_height(ctx.viewport_size.1);
+            self.select.on_resize(self.filter.visible_indices().len(), list_height);
         } else if let Key::Ctrl('f') = key {
             self.filter.enter();
         } else if let State::Idle = self.state {
             match key {
+                Key::Char('u') if self.undo.is_some() => {
+                    self.undo = None;
+                    self.state = State::Waiting(WaitOperation::Undo);
+                    request(ctx, self.query.clone(), Backend::undo);
+                }
                 Key::Char('c') => {
                     if let Some(current_entry_index) = current_entry_index {
                         let entry = &self.entries[current_entry_index];
                         self.state = State::Waiting(WaitOperation::Checkout);
                         let revision = entry.hash.clone();
-                        request(ctx, move |b| b.checkout(&revision));
+                        request(ctx, self.query.clone(), move |b| b.checkout(&revision));
+                    }
+                }
+                Key::Char('C') => {
+                    if let Some(current_entry_index) = current_entry_index {
+                        let source = self.entries[current_entry_index].source.clone();
+                        if !source.is_empty() {
+                            self.state = State::Waiting(WaitOperation::Checkout);
+                            request(ctx, self.query.clone(), move |b| b.checkout(&source));
+                        }
                     }
                 }
+                Key::Char('a') => {
+                    self.state = State::Waiting(WaitOperation::Refresh);
+                    self.query.all = !self.query.all;
+                    ctx.toggles.lock().unwrap().all_refs = self.query.all;
+                    request(ctx, self.query.clone(), |_| Ok(()));
+                }
+                // the graph changes shape so the view starts over
+                Key::Char('M') => {
+                    self.state = State::Waiting(WaitOperation::Refresh);
+                    self.query.no_merges = !self.query.no_merges;
+                    ctx.toggles.lock().unwrap().hide_merges = self.query.no_merges;
+                    self.select.cursor = 0;
+                    request(ctx, self.query.clone(), |_| Ok(()));
+                }
+                Key::Char('F') => {
+                    self.state = State::Waiting(WaitOperation::Refresh);
+                    self.query.first_parent = !self.query.first_parent;
+                    ctx.toggles.lock().unwrap().first_parent = self.query.first_parent;
+                    self.select.cursor = 0;
+                    request(ctx, self.query.clone(), |_| Ok(()));
+                }
                 Key::Char('r') => {
                     if let Some(current_entry_index) = current_entry_index {
-                        let entry = &self.entries[current_entry_index];
-                        self.state = State::Waiting(WaitOperation::Reset);
-                        let revision = entry.hash.clone();
-                        request(ctx, move |b| b.reset(&revision));
+                        let revision = self.entries[current_entry_index].hash.clone();
+                        let confirmed = matches!(&confirm_reset, Some((r, _)) if *r == revision);
+                        self.reset(ctx, revision, confirmed);
                     }
                 }
                 Key::Char('R') => {
-                    self.state = State::Waiting(WaitOperation::Reset);
-                    request(ctx, move |b| b.reset(""));
+                    let confirmed = matches!(&confirm_reset, Some((r, _)) if r.is_empty());
+                    self.reset(ctx, String::new(), confirmed);
                 }
                 Key::Char('m') => {
                     if let Some(current_entry_index) = current_entry_index {
                         let entry = &self.entries[current_entry_index];
                         self.state = State::Waiting(WaitOperation::Merge);
                         let revision = entry.hash.clone();
-                        request(ctx, move |b| b.merge(&revision));
+                        let strategy = ctx.config.merge_strategy;
+                        let c = ctx.clone();
+                        request(ctx, self.query.clone(), move |b| c.record_output(b.merge(&revision, strategy)));
                     }
                 }
+                Key::Char('n') if self.detached => {
+                    let not_empty = true;
+                    let placeholder = "type in the name of the branch to create at HEAD...";
+                    let on_submit = |ctx: &ModeContext, name: String| {
+                        ctx.event_sender.send_response(ModeResponse::Log(Response::NewBranch(name)));
+                    };
+                    ctx.event_sender.send_mode_change(
+                        ModeKind::MessageInput,
+                        ModeChangeInfo::message_input(ModeKind::Log, not_empty, placeholder, on_submit),
+                    );
+                }
+                Key::Char('w') => {
+                    if let Some(current_entry_index) = current_entry_index {
+                        self.state = State::Waiting(WaitOperation::Reword);
+                        let revision = self.entries[current_entry_index].hash.clone();
+                        let ctx = ctx.clone();
+                        thread::spawn(move || {
+                            let message = ctx.backend.revision_details(&revision).map(|info| info.message);
+                            ctx.event_sender.send_response(ModeResponse::Log(Response::RewordMessage(revision, message)));
+                        });
+                    }
+                }
+                Key::Char('i') => {
+                    if let Some(current_entry_index) = current_entry_index {
+                        let base = self.entries[current_entry_index].hash.clone();
+                        ctx.event_sender.send_mode_change(ModeKind::Rebase, ModeChangeInfo::rebase(ModeKind::Log, base));
+                    }
+                }
+                Key::Char('/') | Key::Char('?') => {
+                    let not_empty = true;
+                    if key == Key::Char('/') {
+                        let placeholder = "type in the text added or removed by the commits...";
+                        let on_submit = |ctx: &ModeContext, term: String| {
+                            ctx.event_sender.send_response(ModeResponse::Log(Response::Pickaxe(term, false)));
+                        };
+                        ctx.event_sender.send_mode_change(
+                            ModeKind::MessageInput,
+                            ModeChangeInfo::message_input(ModeKind::Log, not_empty, placeholder, on_submit),
+                        );
+                    } else {
+                        let placeholder = "type in the regex matching lines changed by the commits...";
+                        let on_submit = |ctx: &ModeContext, term: String| {
+                            ctx.event_sender.send_response(ModeResponse::Log(Response::Pickaxe(term, true)));
+                        };
+                        ctx.event_sender.send_mode_change(
+                            ModeKind::MessageInput,
+                            ModeChangeInfo::message_input(ModeKind::Log, not_empty, placeholder, on_submit),
+                        );
+                    }
+                }
+                Key::Esc if self.query.pickaxe.is_some() => {
+                    self.state = State::Waiting(WaitOperation::Refresh);
+                    self.query.pickaxe = None;
+                    self.select.cursor = 0;
+                    request(ctx, self.query.clone(), |_| Ok(()));
+                    return ModeStatus { pending_input: true };
+                }
                 Key::Char('f') => {
                     self.state = State::Waiting(WaitOperation::Fetch);
-                    request(ctx, Backend::fetch);
+                    let c = ctx.clone();
+                    request(ctx, self.query.clone(), move |b| {
+                        let result = b.fetch().map(|(output, pruned)| {
+                            c.event_sender.send_response(ModeResponse::Log(Response::Pruned(pruned)));
+                            output
+                        });
+                        c.record_output(result)
+                    });
                 }
                 Key::Char('p') => {
                     self.state = State::Waiting(WaitOperation::Pull);
-                    request(ctx, Backend::pull);
+                    let c = ctx.clone();
+                    request(ctx, self.query.clone(), move |b| c.record_output(b.pull()));
                 }
                 Key::Char('P') => {
                     self.state = State::Waiting(WaitOperation::Push);
-                    request(ctx, Backend::push);
+                    let c = ctx.clone();
+                    request(ctx, self.query.clone(), move |b| c.record_output(b.push()));
+                }
+                // toggles between the log and what a push would send
+                Key::Char('o') if !self.output.text().is_empty() => self.output.set(String::new()),
+                Key::Char('o') => {
+                    self.state = State::Waiting(WaitOperation::PushPreview);
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let output = match ctx.backend.push_preview() {
+                            Ok(output) => output,
+                            Err(error) => error,
+                        };
+                        ctx.event_sender.send_response(ModeResponse::Log(Response::PushPreview(output)));
+                    });
                 }
                 Key::Char('g') => {
                     self.state = State::Waiting(WaitOperation::Push);
-                    request(ctx, Backend::push_gerrit); // push to gerrit
+                    let c = ctx.clone();
+                    request(ctx, self.query.clone(), move |b| c.record_output(b.push_gerrit()));
+                    // push to gerrit
+                }
+                Key::Char('G') => {
+                    self.state = State::Waiting(WaitOperation::Gc);
+                    // runs on this thread so git can show its progress directly on the terminal
+                    let result = Platform::suspend(|| ctx.backend.gc());
+                    request(ctx, self.query.clone(), move |_| result);
                 }
                 _ => (),
             }
@@ -234,13 +516,21 @@ impl ModeTrait for Mode {
         ModeStatus { pending_input: false }
     }
 
-    fn on_response(&mut self, _ctx: &ModeContext, response: ModeResponse) {
+    fn on_response(&mut self, ctx: &ModeContext, response: ModeResponse) {
         let response = as_variant!(response, ModeResponse::Log).unwrap();
         match response {
             Response::Refresh(result) => {
                 self.output.set(String::new());
 
-                if let State::Waiting(_) = self.state {
+                if let State::Waiting(operation) = &self.state {
+                    self.undo = match operation {
+                        WaitOperation::Reset | WaitOperation::Merge | WaitOperation::Reword => ctx.backend.undo_description(),
+                        WaitOperation::Refresh => self.undo.take(),
+                        _ => None,
+                    };
+                    if !matches!(operation, WaitOperation::Refresh | WaitOperation::Fetch) {
+                        self.pruned.clear();
+                    }
                     self.state = State::Idle;
                 }
                 if let State::Idle = self.state {
@@ -259,6 +549,70 @@ impl ModeTrait for Mode {
                 self.filter.filter(self.entries.iter());
                 self.select.saturate_cursor(self.filter.visible_indices().len());
             }
+            Response::RewordMessage(revision, result) => match result {
+                Ok(message) => {
+                    self.state = State::Idle;
+
+                    // the input is a single line so only the summary is edited and the body is kept
+                    let summary = message.lines().next().unwrap_or("").to_owned();
+                    self.reword = Some((revision, message));
+
+                    let not_empty = true;
+                    let placeholder = "type in the new commit message...";
+                    let on_submit = |ctx: &ModeContext, message: String| {
+                        ctx.event_sender.send_response(ModeResponse::Log(Response::Reword(message)));
+                    };
+                    ctx.event_sender.send_mode_change(
+                        ModeKind::MessageInput,
+                        ModeChangeInfo::message_input_with_text(ModeKind::Log, not_empty, placeholder, summary, on_submit)
+                            .commit_message(),
+                    );
+                }
+                Err(error) => {
+                    self.state = State::Idle;
+                    self.output.set(error);
+                }
+            },
+            Response::Detached(detached) => self.detached = detached,
+            Response::Pruned(pruned) => self.pruned = pruned,
+            Response::NewBranch(name) => {
+                self.state = State::Waiting(WaitOperation::NewBranch);
+                request(ctx, self.query.clone(), move |b| b.new_branch(&name));
+            }
+            Response::PushPreview(output) => {
+                self.state = State::Idle;
+                self.output.set(output);
+            }
+            Response::Pickaxe(term, regex) => {
+                self.state = State::Waiting(WaitOperation::Refresh);
+                self.query.pickaxe = Some((term, regex));
+                self.select.cursor = 0;
+                request(ctx, self.query.clone(), |_| Ok(()));
+            }
+            Response::ResetPreview(revision, result) => match result {
+                // nothing would be lost so there is nothing to confirm
+                Ok(entries) if entries.is_empty() => self.reset(ctx, revision, true),
+                Ok(entries) => {
+                    self.state = State::Idle;
+                    self.confirm_reset = Some((revision, entries));
+                }
+                Err(error) => {
+                    self.state = State::Idle;
+                    self.output.set(error);
+                }
+            },
+            Response::Reword(summary) => {
+                if let Some((revision, original)) = self.reword.take() {
+                    let mut message = summary;
+                    if let Some((_, body)) = original.split_once('\n') {
+                        message.push('\n');
+                        message.push_str(body);
+                    }
+
+                    self.state = State::Waiting(WaitOperation::Reword);
+                    request(ctx, self.query.clone(), move |b| b.reword(&revision, &message));
+                }
+            }
         }
     }
 
@@ -269,38 +623,159 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let list_height = self.list_height(size.1);
+        self.select.on_resize(self.filter.visible_indices().len(), list_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
         let name = match self.state {
-            State::Idle | State::Waiting(WaitOperation::Refresh) => "log",
+            State::Idle | State::Waiting(WaitOperation::Refresh) => match (self.query.first_parent, self.query.no_merges) {
+                (true, true) => "first parent log without merges",
+                (true, false) => "first parent log",
+                (false, true) => "log without merges",
+                (false, false) => "log",
+            },
             State::Waiting(WaitOperation::Reset) => "reset",
+            State::Waiting(WaitOperation::Reword) => "reword",
             State::Waiting(WaitOperation::Checkout) => "checkout",
             State::Waiting(WaitOperation::Merge) => "merge",
             State::Waiting(WaitOperation::Fetch) => "fetch",
             State::Waiting(WaitOperation::Pull) => "pull",
             State::Waiting(WaitOperation::Push) => "push",
+            State::Waiting(WaitOperation::PushPreview) => "push preview",
+            State::Waiting(WaitOperation::Gc) => "gc",
+            State::Waiting(WaitOperation::Undo) => "undo",
+            State::Waiting(WaitOperation::NewBranch) => "new branch",
         };
 
-        let left_help = "[c]checkout [enter]details [f]fetch [p]pull [P]push [g]gerrit [r]reset [R]reset to remote";
-        let right_help = "[tab]full message [Left]back [arrows]move [ctrl+f]filter";
+        let left_help =
+            "[c]checkout [enter]details [f]fetch [p]pull [P]push [o]push preview [g]gerrit [G]gc [r]reset [R]reset to remote [w]reword [i]rebase onto";
+        let right_help =
+            "[/]search changes [?]search changes by regex [a]all refs [M]hide merges [F]first parent [C]checkout source [tab]full message [B]body [</>]resize body [e]email [h]full hash [Left]back [arrows]move [ctrl+f]filter";
         (name, left_help, right_help)
     }
 
     fn draw(&self, drawer: &mut Drawer) {
-        let filter_line_count = drawer.filter(&self.filter);
-        if self.output.text().is_empty() {
+        let mut filter_line_count = drawer.filter(&self.filter);
+        if let Some(undo) = &self.undo {
+            drawer.fmt(format_args!("{}press [u] to undo: {}{}", Color::DarkYellow, undo, Color::White));
+            drawer.next_line();
+            filter_line_count += 1;
+        }
+        if !self.pruned.is_empty() {
+            drawer.fmt(format_args!(
+                "{}fetch pruned the deleted remote branches: {}{}",
+                Color::DarkYellow,
+                self.pruned.join(", "),
+                Color::White
+            ));
+            drawer.next_line();
+            filter_line_count += 1;
+        }
+        if self.detached {
+            drawer.fmt(format_args!(
+                "{}HEAD is detached, press [n] to create a branch here so new commits are not lost{}",
+                Color::DarkYellow,
+                Color::White
+            ));
+            drawer.next_line();
+            filter_line_count += 1;
+        }
+        if let Some((term, regex)) = &self.query.pickaxe {
+            let what = if *regex { "change lines matching" } else { "add or remove" };
+            drawer.fmt(format_args!(
+                "{}commits that {} '{}', press [esc] to show all{}",
+                Color::DarkYellow,
+                what,
+                term,
+                Color::White
+            ));
+            drawer.next_line();
+            filter_line_count += 1;
+        }
+
+        if let Some((revision, entries)) = &self.confirm_reset {
+            let key = if revision.is_empty() { 'R' } else { 'r' };
+            drawer.fmt(format_args!(
+                "{}the reset drops these {} commit(s), press [{}] again to reset anyway{}",
+                Color::DarkYellow,
+                entries.len(),
+                key,
+                Color::White
+            ));
+            drawer.next_line();
+            let width = drawer.viewport_size.0 as usize;
+            let height = (drawer.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT + filter_line_count + 1);
+            for entry in entries.iter().take(height) {
+                let message = entry.message.lines().next().unwrap_or("");
+                let message = match message.char_indices().nth(width.saturating_sub(entry.hash.len() + 1)) {
+                    Some((i, _)) => &message[..i],
+                    None => message,
+                };
+                drawer.fmt(format_args!("{}{} {}{}", Color::DarkYellow, entry.hash, Color::White, message));
+                drawer.next_line();
+            }
+        } else if self.entries.is_empty() && self.output.text().is_empty() {
+            if let State::Idle = self.state {
+                let empty_message = match self.query.pickaxe {
+                    Some(_) => "no commits found! press [esc] to show all",
+                    None => "no commits yet! press [c] in status to make the first one",
+                };
+                drawer.fmt(format_args!("{}{}", Color::DarkYellow, empty_message));
+            }
+        } else if self.output.text().is_empty() {
+            let entries: Vec<_> = self
+                .filter
+                .visible_indices()
+                .iter()
+                .map(|&i| LogEntryView {
+                    entry: &self.entries[i],
+                    show_email: self.show_email,
+                    show_full_hash: self.show_full_hash,
+                })
+                .collect();
+            let preview_line_count = self.preview_line_count();
             drawer.select_menu(
                 &self.select,
-                filter_line_count,
+                filter_line_count + preview_line_count,
                 self.show_full_hovered_message,
-                self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
+                entries.iter(),
             );
+
+            if self.show_body {
+                let body = match self.filter.get_visible_index(self.select.cursor) {
+                    Some(i) if !self.entries[i].body.is_empty() => &self.entries[i].body[..],
+                    Some(_) => "(no body)",
+                    None => "",
+                };
+                let width = drawer.viewport_size.0 as usize;
+                drawer.fmt(format_args!("{}{}{}", Color::DarkBlue, "-".repeat(width), Color::White));
+                drawer.next_line();
+                for line in body.lines().take(preview_line_count - 1) {
+                    let line = match line.char_indices().nth(width) {
+                        Some((i, _)) => &line[..i],
+                        None => line,
+                    };
+                    drawer.str(line);
+                    drawer.next_line();
+                }
+            }
         } else {
             drawer.output(&self.output);
         }
     }
 }
 
-fn request<F>(ctx: &ModeContext, f: F)
+fn request<F>(ctx: &ModeContext, query: LogQuery, f: F)
+where
+    F: 'static + Send + Sync + FnOnce(&dyn Backend) -> BackendResult<()>,
+{
+    request_entries(ctx, query, 0, f);
+}
+
+// lists at least a screen of entries from the top
+fn request_entries<F>(ctx: &ModeContext, query: LogQuery, min_len: usize, f: F)
 where
     F: 'static + Send + Sync + FnOnce(&dyn Backend) -> BackendResult<()>,
 {
@@ -309,8 +784,85 @@ where
         use std::ops::Deref;
 
         let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
-        let result = f(ctx.backend.deref()).and_then(|_| ctx.backend.log(0, available_height));
+        let len = available_height.max(min_len);
+        let result = f(ctx.backend.deref()).and_then(|_| query.entries(ctx.backend.deref(), 0, len));
         //println!("result: {:?}", result);
+        match ctx.backend.head_is_detached() {
+            Ok(detached) => ctx.event_sender.send_response(ModeResponse::Log(Response::Detached(detached))),
+            Err(error) => ctx.event_sender.send_notice(error.lines().last().unwrap_or("").into()),
+        }
         ctx.event_sender.send_response(ModeResponse::Log(Response::Refresh(result)));
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+
+    #[test]
+    fn refresh_keeps_paged_entries() {
+        let log_entries = (0..100).map(|i| LogEntry { hash: i.to_string(), ..Default::default() }).collect();
+        let backend = Arc::new(FakeBackend { log_entries, ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend);
+        let mut mode = Mode::default();
+        mode.on_enter(&ctx, ModeChangeInfo::new(ModeKind::Log));
+        while mode.is_waiting_response() {
+            mode.on_response(&ctx, receiver.recv_response().unwrap());
+        }
+        let page_len = mode.entries.len();
+
+        // reaching the last entry loads the next page
+        for _ in 0..page_len + 5 {
+            mode.on_key(&ctx, Key::Down);
+            while mode.is_waiting_response() {
+                mode.on_response(&ctx, receiver.recv_response().unwrap());
+            }
+        }
+        let entry_count = mode.entries.len();
+        let cursor = mode.select.cursor;
+        assert!(entry_count > page_len);
+        assert_eq!(cursor, page_len + 5);
+
+        mode.refresh(&ctx);
+        assert!(mode.is_waiting_response());
+        while mode.is_waiting_response() {
+            mode.on_response(&ctx, receiver.recv_response().unwrap());
+        }
+        assert_eq!(mode.entries.len(), entry_count);
+        assert_eq!(mode.select.cursor, cursor);
+    }
+
+    #[test]
+    fn pickaxe_search_is_paged() {
+        let log_entries = (0..100).map(|i| LogEntry { hash: i.to_string(), ..Default::default() }).collect();
+        let backend = Arc::new(FakeBackend { log_entries, ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = Mode::default();
+        mode.on_enter(&ctx, ModeChangeInfo::new(ModeKind::Log));
+        while mode.is_waiting_response() {
+            mode.on_response(&ctx, receiver.recv_response().unwrap());
+        }
+
+        mode.on_response(&ctx, ModeResponse::Log(Response::Pickaxe("term".into(), false)));
+        while mode.is_waiting_response() {
+            mode.on_response(&ctx, receiver.recv_response().unwrap());
+        }
+        let page_len = mode.entries.len();
+        assert!(page_len < 100);
+
+        for _ in 0..page_len {
+            mode.on_key(&ctx, Key::Down);
+            while mode.is_waiting_response() {
+                mode.on_response(&ctx, receiver.recv_response().unwrap());
+            }
+        }
+        assert!(mode.entries.len() > page_len);
+        // only the first page of the plain log, before the search
+        let calls = backend.calls();
+        assert_eq!(calls.iter().filter(|c| *c == "log").count(), 1);
+        assert!(calls.iter().filter(|c| *c == "log_pickaxe").count() > 1);
+    }
+}
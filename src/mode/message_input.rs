@@ -1,4 +1,8 @@
-use crate::{mode::*, platform::Key, ui::Drawer};
+use crate::{
+    mode::*,
+    platform::Key,
+    ui::{Color, Drawer, RESERVED_LINES_COUNT},
+};
 use std::fmt;
 
 pub enum Response {}
@@ -20,11 +24,23 @@ impl fmt::Debug for OnSubmit {
 pub struct ModeInfo {
     pub not_empty: bool, // the submit string must be not empty
     pub placeholder: String,
+    pub text: String, // initial input
     pub on_submit: OnSubmit,
+    pub commit_message: bool, // shows how the subject length compares to git conventions
+    pub error: String,
+    pub preview: String, // what submitting acts on
 }
 impl ModeInfo {
     pub fn new(not_empty: bool, placeholder: String, on_submit: fn(ctx: &ModeContext, message: String)) -> Self {
-        Self { not_empty, placeholder, on_submit: OnSubmit(on_submit) }
+        Self {
+            not_empty,
+            placeholder,
+            text: String::new(),
+            on_submit: OnSubmit(on_submit),
+            commit_message: false,
+            error: String::new(),
+            preview: String::new(),
+        }
     }
 }
 
@@ -35,16 +51,22 @@ pub struct Mode {
     placeholder: String,
     on_submit: OnSubmit,
     not_empty: bool,
+    commit_message: bool,
+    error: String,
+    preview: String,
 }
 
 impl ModeTrait for Mode {
     fn on_enter(&mut self, _ctx: &ModeContext, info: ModeChangeInfo) {
-        self.readline.clear();
         self.from = info.from;
         let mode_info = as_variant!(info.info.unwrap(), super::ModeInfo::MessageInput).unwrap();
+        self.readline.set(&mode_info.text);
         self.placeholder = mode_info.placeholder;
         self.on_submit = mode_info.on_submit;
         self.not_empty = mode_info.not_empty;
+        self.commit_message = mode_info.commit_message;
+        self.error = mode_info.error;
+        self.preview = mode_info.preview;
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
@@ -70,11 +92,52 @@ impl ModeTrait for Mode {
         false
     }
 
+    fn on_resize(&mut self, _size: (u16, u16)) {}
+
     fn header(&self) -> (&str, &str, &str) {
         ("message input", "[enter]submit [Esc]cancel", "[Left]back")
     }
 
     fn draw(&self, drawer: &mut Drawer) {
         drawer.readline(&self.readline, &self.placeholder);
+
+        if self.commit_message {
+            // subjects should fit in 50 chars and never go past 72
+            let len = self.readline.input().chars().count();
+            let color = match len {
+                0..=50 => Color::DarkGray,
+                51..=72 => Color::DarkYellow,
+                _ => Color::DarkRed,
+            };
+            drawer.next_line();
+            drawer.next_line();
+            drawer.fmt(format_args!("{}subject length {}/50{}", color, len, Color::White));
+        }
+
+        if !self.error.is_empty() {
+            drawer.next_line();
+            drawer.next_line();
+            drawer.fmt(format_args!("{}", Color::DarkRed));
+            for line in self.error.lines() {
+                drawer.str(line);
+                drawer.next_line();
+            }
+            drawer.fmt(format_args!("{}", Color::White));
+        }
+
+        if !self.preview.is_empty() {
+            drawer.next_line();
+            drawer.next_line();
+            let height = (drawer.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT + 2);
+            let line_count = self.preview.lines().count();
+            for (i, line) in self.preview.lines().enumerate() {
+                if i + 1 == height && line_count > height {
+                    drawer.fmt(format_args!("{}and {} more...{}", Color::DarkGray, line_count - i, Color::White));
+                    break;
+                }
+                drawer.str(line);
+                drawer.next_line();
+            }
+        }
     }
 }
@@ -0,0 +1,214 @@
+use std::thread;
+
+use crate::{
+    backend::{BackendResult, RebaseAction, RebaseEntry},
+    mode::*,
+    platform::{Key, Platform},
+    ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
+};
+
+pub enum Response {
+    Refresh(BackendResult<Vec<RebaseEntry>>),
+    Done,
+}
+
+#[derive(Default, Clone, Debug)]
+enum State {
+    #[default]
+    Idle,
+    Waiting,
+}
+
+impl SelectEntryDraw for RebaseEntry {
+    fn draw(&self, drawer: &mut Drawer, hovered: bool, _: bool) -> usize {
+        let action_color = match self.action {
+            RebaseAction::Pick => Color::White,
+            RebaseAction::Reword => Color::DarkGreen,
+            RebaseAction::Squash | RebaseAction::Fixup => Color::DarkBlue,
+            RebaseAction::Drop => Color::DarkRed,
+            RebaseAction::Other(_) => Color::DarkGray,
+        };
+        let (action_color, hash_color) =
+            if hovered { (Color::White, Color::White) } else { (action_color, Color::DarkYellow) };
+
+        drawer.fmt(format_args!(
+            "{}{:<width$} {}{} {}{}",
+            action_color,
+            self.action.as_str(),
+            hash_color,
+            &self.hash,
+            Color::White,
+            &self.message,
+            width = RebaseAction::max_len(),
+        ));
+        1
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Mode {
+    state: State,
+    entries: Vec<RebaseEntry>,
+    output: Output,
+    select: SelectMenu,
+    count: Count,
+    base: String,
+    from: ModeKind,
+}
+impl Mode {
+    fn rebase(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting;
+
+        let entries = self.entries.clone();
+        let base = self.base.clone();
+
+        if entries.iter().any(|e| e.action.needs_editor()) {
+            // runs on this thread as nothing should be drawn while the editor owns the terminal
+            let result = Platform::suspend(|| ctx.backend.rebase_interactive(&base, &entries, true));
+            on_rebase_result(ctx, result);
+        } else {
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let result = ctx.backend.rebase_interactive(&base, &entries, false);
+                on_rebase_result(&ctx, result);
+            });
+        }
+    }
+}
+
+impl ModeTrait for Mode {
+    fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo) {
+        if let State::Waiting = self.state {
+            return;
+        }
+        self.state = State::Waiting;
+
+        self.output.set(String::new());
+        self.entries.clear();
+        self.select.cursor = 0;
+        self.from = info.from;
+        self.base = as_variant!(info.info.unwrap(), ModeInfo::Rebase).unwrap();
+
+        let ctx = ctx.clone();
+        let base = self.base.clone();
+        thread::spawn(move || {
+            let result = ctx.backend.rebase_todo(&base);
+            ctx.event_sender.send_response(ModeResponse::Rebase(Response::Refresh(result)));
+        });
+    }
+
+    fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
+        let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        if !self.output.text().is_empty() {
+            self.output.on_key(available_height, count, key);
+            return ModeStatus { pending_input: false };
+        }
+        if !matches!(self.state, State::Idle) || self.entries.is_empty() {
+            return ModeStatus { pending_input: false };
+        }
+
+        self.select.on_key(self.entries.len(), available_height, count, key);
+
+        let cursor = self.select.cursor;
+        let action = match key {
+            Key::Char('p') => Some(RebaseAction::Pick),
+            Key::Char('r') => Some(RebaseAction::Reword),
+            Key::Char('s') => Some(RebaseAction::Squash),
+            Key::Char('f') => Some(RebaseAction::Fixup),
+            Key::Char('d') => Some(RebaseAction::Drop),
+            _ => None,
+        };
+        if let Some(action) = action {
+            // only commits can have their action changed
+            let entry = &mut self.entries[cursor];
+            if !matches!(entry.action, RebaseAction::Other(_)) {
+                entry.action = action;
+            }
+            // keeps 's' from switching to status
+            return ModeStatus { pending_input: true };
+        }
+
+        match key {
+            Key::Char('K') if cursor > 0 => {
+                self.entries.swap(cursor, cursor - 1);
+                self.select.on_key(self.entries.len(), available_height, 1, Key::Up);
+            }
+            Key::Char('J') if cursor + 1 < self.entries.len() => {
+                self.entries.swap(cursor, cursor + 1);
+                self.select.on_key(self.entries.len(), available_height, 1, Key::Down);
+            }
+            Key::Enter => self.rebase(ctx),
+            _ => (),
+        }
+
+        ModeStatus { pending_input: false }
+    }
+
+    fn on_response(&mut self, _ctx: &ModeContext, response: ModeResponse) {
+        let response = as_variant!(response, ModeResponse::Rebase).unwrap();
+        match response {
+            Response::Refresh(result) => {
+                self.state = State::Idle;
+                match result {
+                    Ok(entries) => {
+                        self.output.set(String::new());
+                        self.entries = entries;
+                    }
+                    Err(error) => {
+                        self.output.set(error);
+                        self.entries.clear();
+                    }
+                }
+                self.select.saturate_cursor(self.entries.len());
+            }
+            Response::Done => self.state = State::Idle,
+        }
+    }
+
+    fn is_waiting_response(&self) -> bool {
+        match self.state {
+            State::Idle => false,
+            State::Waiting => true,
+        }
+    }
+
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.select.on_resize(self.entries.len(), available_height);
+        self.output.on_resize(available_height);
+    }
+
+    fn header(&self) -> (&str, &str, &str) {
+        (
+            "interactive rebase",
+            "[enter]rebase [p]pick [r]reword [s]squash [f]fixup [d]drop",
+            "[J/K]move entry [Left]back [arrows]move",
+        )
+    }
+
+    fn draw(&self, drawer: &mut Drawer) {
+        if !self.output.text().is_empty() {
+            drawer.output(&self.output);
+        } else if self.entries.is_empty() {
+            if let State::Idle = self.state {
+                drawer.fmt(format_args!("{}nothing to rebase!", Color::DarkYellow));
+            }
+        } else {
+            drawer.select_menu(&self.select, 0, false, self.entries.iter());
+        }
+    }
+}
+
+fn on_rebase_result(ctx: &ModeContext, result: BackendResult<()>) {
+    match result {
+        Ok(()) => {
+            ctx.event_sender.send_response(ModeResponse::Rebase(Response::Done));
+            ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Rebase));
+        }
+        Err(error) => ctx.event_sender.send_response(ModeResponse::Rebase(Response::Refresh(Err(error)))),
+    }
+}
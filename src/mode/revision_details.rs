@@ -1,14 +1,16 @@
 use std::thread;
 
 use crate::{
-    backend::{RevisionEntry, RevisionInfo},
+    backend::{Backend, BackendResult, FileStatus, RevisionEntry, RevisionInfo},
     mode::*,
     platform::Key,
-    ui::{Drawer, RESERVED_LINES_COUNT},
+    ui::{Color, Drawer, RESERVED_LINES_COUNT},
 };
 
 pub enum Response {
-    Info(RevisionInfo),
+    Info(RevisionInfo, String), // info, note
+    AddNote(String),
+    Restore(BackendResult<String>), // restored path
 }
 
 #[derive(Clone, Debug)]
@@ -28,12 +30,38 @@ pub struct Mode {
     entries: Vec<RevisionEntry>,
     output: Output,
     select: SelectMenu,
+    count: Count,
     filter: Filter,
     show_full_message: bool,
     revision: String,
+    parent_count: usize,
+    note: String,
     from: ModeKind,
+    confirm_restore: Option<String>, // path waiting for a second [r] as restoring overwrites local changes
 }
 impl Mode {
+    fn request<F>(&mut self, ctx: &ModeContext, f: F)
+    where
+        F: 'static + Send + Sync + FnOnce(&dyn Backend) -> BackendResult<()>,
+    {
+        self.state = State::Waiting;
+
+        let ctx = ctx.clone();
+        let revision = self.revision.clone();
+        thread::spawn(move || {
+            use std::ops::Deref;
+
+            let mut info = match f(ctx.backend.deref()).and_then(|_| ctx.backend.revision_details(&revision)) {
+                Ok(info) => info,
+                Err(error) => RevisionInfo { message: error, ..Default::default() },
+            };
+            info.entries.sort_unstable_by(|a, b| a.status.cmp(&b.status));
+            let note = ctx.backend.notes_show(&revision).unwrap_or_default();
+
+            ctx.event_sender.send_response(ModeResponse::RevisionDetails(Response::Info(info, note)));
+        });
+    }
+
     fn get_selected_entries(&self) -> Vec<RevisionEntry> {
         self.entries.iter().filter(|&e| e.selected).cloned().collect()
     }
@@ -44,7 +72,6 @@ impl ModeTrait for Mode {
         if let State::Waiting = self.state {
             return;
         }
-        self.state = State::Waiting;
 
         self.output.set(String::new());
         self.filter.clear();
@@ -53,17 +80,7 @@ impl ModeTrait for Mode {
         self.from = info.from;
         self.revision = as_variant!(info.info.unwrap(), ModeInfo::RevisionDetails).unwrap();
 
-        let ctx = ctx.clone();
-        let revision = self.revision.clone();
-        thread::spawn(move || {
-            let mut info = match ctx.backend.revision_details(&revision) {
-                Ok(info) => info,
-                Err(error) => RevisionInfo { message: error, entries: Vec::new() },
-            };
-            info.entries.sort_unstable_by(|a, b| a.status.cmp(&b.status));
-
-            ctx.event_sender.send_response(ModeResponse::RevisionDetails(Response::Info(info)));
-        });
+        self.request(ctx, |_| Ok(()));
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
@@ -75,6 +92,11 @@ impl ModeTrait for Mode {
             return ModeStatus { pending_input: true };
         }
 
+        let confirm_restore = self.confirm_restore.take();
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
         if let State::Idle = self.state {
             let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
             let line_count = if self.show_full_message { self.output.line_count() } else { 1 };
@@ -82,6 +104,7 @@ impl ModeTrait for Mode {
             match self.select.on_key(
                 self.filter.visible_indices().len(),
                 available_height.saturating_sub(line_count + 1),
+                count,
                 key,
             ) {
                 SelectMenuAction::None => (),
@@ -103,23 +126,69 @@ impl ModeTrait for Mode {
                 Key::Tab => {
                     self.show_full_message = !self.show_full_message;
                 }
+                Key::Char('v') => {
+                    if let Some(i) = self.filter.get_visible_index(self.select.cursor) {
+                        let path = self.entries[i].name.clone();
+                        ctx.event_sender.send_mode_change(
+                            ModeKind::FileView,
+                            ModeChangeInfo::file(ModeKind::RevisionDetails, self.revision.clone(), path),
+                        );
+                    }
+                }
                 Key::Enter => {
                     if !self.entries.is_empty() {
                         let entries = self.get_selected_entries();
-                        let ctx = ctx.clone();
-                        let revision = self.revision.clone();
+                        let request = diff::DiffRequest::Revision(self.revision.clone(), self.parent_count, entries);
+                        ctx.event_sender
+                            .send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::RevisionDetails, request));
+                    }
+                }
+                Key::Char('n') => {
+                    // the input is a single line, so a multiline note is edited as one
+                    let note = self.note.lines().collect::<Vec<_>>().join(" ");
+                    let not_empty = true;
+                    let placeholder = "type in the note...";
+                    let on_submit = |ctx: &ModeContext, note: String| {
+                        ctx.event_sender.send_response(ModeResponse::RevisionDetails(Response::AddNote(note)));
+                    };
+                    ctx.event_sender.send_mode_change(
+                        ModeKind::MessageInput,
+                        ModeChangeInfo::message_input_with_text(
+                            ModeKind::RevisionDetails,
+                            not_empty,
+                            placeholder,
+                            note,
+                            on_submit,
+                        ),
+                    );
+                }
+                Key::Char('r') => {
+                    if let Some(i) = self.filter.get_visible_index(self.select.cursor) {
+                        let path = self.entries[i].name.clone();
+                        if confirm_restore.as_ref() != Some(&path) && !ctx.config.expert_mode {
+                            self.confirm_restore = Some(path);
+                            return ModeStatus { pending_input: false };
+                        }
 
+                        self.state = State::Waiting;
+                        // a file deleted by the revision is brought back as it was right before
+                        let revision = match self.entries[i].status {
+                            FileStatus::Deleted => format!("{}^", self.revision),
+                            _ => self.revision.clone(),
+                        };
+                        let ctx = ctx.clone();
                         thread::spawn(move || {
-                            ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::new(ModeKind::RevisionDetails));
-
-                            let output = match ctx.backend.diff(Some(&revision), &entries) {
-                                Ok(output) => output,
-                                Err(error) => error,
-                            };
-                            ctx.event_sender.send_response(ModeResponse::Diff(diff::Response::Refresh(output)));
+                            let result = ctx.backend.restore_file_from(&revision, &path).map(|_| path);
+                            ctx.event_sender.send_response(ModeResponse::RevisionDetails(Response::Restore(result)));
                         });
                     }
                 }
+                Key::Char('R') if !self.entries.is_empty() => {
+                    let entries = self.get_selected_entries();
+                    let request = diff::DiffRequest::Renames(Some(self.revision.clone()), entries);
+                    ctx.event_sender
+                        .send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::RevisionDetails, request));
+                }
                 _ => (),
             }
         }
@@ -127,20 +196,38 @@ impl ModeTrait for Mode {
         ModeStatus { pending_input: false }
     }
 
-    fn on_response(&mut self, _ctx: &ModeContext, response: ModeResponse) {
+    fn on_response(&mut self, ctx: &ModeContext, response: ModeResponse) {
         let response = as_variant!(response, ModeResponse::RevisionDetails).unwrap();
         match response {
-            Response::Info(info) => {
+            Response::Info(info, note) => {
                 if let State::Waiting = self.state {
                     self.state = State::Idle;
                 }
 
-                self.output.set(info.message);
+                let mut message = info.message;
+                if !note.is_empty() {
+                    message.push_str("\n\nnotes:\n");
+                    message.push_str(&note);
+                }
+                self.output.set(message);
+                self.note = note;
                 self.entries = info.entries;
+                self.parent_count = info.parent_count;
 
                 self.filter.filter(self.entries.iter());
                 self.select.saturate_cursor(self.filter.visible_indices().len());
             }
+            Response::AddNote(note) => {
+                let revision = self.revision.clone();
+                self.request(ctx, move |b| b.notes_add(&revision, &note));
+            }
+            Response::Restore(result) => {
+                self.state = State::Idle;
+                ctx.event_sender.send_notice(match result {
+                    Ok(path) => format!("restored '{}'", path),
+                    Err(error) => error.lines().last().unwrap_or("").into(),
+                });
+            }
         }
     }
 
@@ -151,10 +238,17 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        let line_count = if self.show_full_message { self.output.line_count() } else { 1 };
+        self.select.on_resize(self.filter.visible_indices().len(), available_height.saturating_sub(line_count + 1));
+        self.output.on_resize(available_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
         (
             "revision details",
-            "[enter]diff",
+            "[enter]diff [R]diff renames [v]view file [r]restore file [n]note",
             "[tab]full message [Left]back [arrows]move [space]toggle [a]toggle all [ctrl+f]filter",
         )
     }
@@ -178,6 +272,14 @@ impl ModeTrait for Mode {
         let line_count = filter_line_count + line_count;
 
         if let State::Idle = self.state {
+            if let Some(path) = &self.confirm_restore {
+                drawer.fmt(format_args!(
+                    "{}press [r] again to overwrite '{}' in the working tree with its version from this revision{}",
+                    Color::DarkYellow,
+                    path,
+                    Color::White
+                ));
+            }
             drawer.next_line();
             drawer.select_menu(
                 &self.select,
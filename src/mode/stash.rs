@@ -1,4 +1,4 @@
-use std::thread;
+use std::{cmp::Reverse, thread};
 
 use crate::{
     backend::{Backend, BackendResult, StashEntry},
@@ -39,9 +39,11 @@ impl SelectEntryDraw for StashEntry {
         }
 
         drawer.fmt(format_args!(
-            "{}[{}] {}{} {}{}",
+            "{}[{}] {}{:>7} {}{} {}{}",
             color(Color::DarkYellow, hovered),
             self.id,
+            color(Color::DarkBlue, hovered),
+            relative_time(self.timestamp),
             color(Color::DarkGreen, hovered),
             &self.branch,
             color(Color::White, hovered),
@@ -56,7 +58,19 @@ pub struct Mode {
     entries: Vec<StashEntry>,
     output: Output,
     select: SelectMenu,
+    count: Count,
     filter: Filter,
+    sort_by_time: bool,
+}
+
+impl Mode {
+    fn sort_entries(&mut self) {
+        if self.sort_by_time {
+            self.entries.sort_by_key(|e| Reverse(e.timestamp));
+        } else {
+            self.entries.sort_by_key(|e| e.id);
+        }
+    }
 }
 
 impl ModeTrait for Mode {
@@ -73,6 +87,15 @@ impl ModeTrait for Mode {
         request(ctx, |_| Ok(()));
     }
 
+    fn is_showing_output(&self) -> bool {
+        !self.output.text().is_empty()
+    }
+
+    fn request_refresh(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Refresh);
+        request(ctx, |_| Ok(()));
+    }
+
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
         if self.filter.has_focus() {
             self.filter.on_key(key);
@@ -82,11 +105,15 @@ impl ModeTrait for Mode {
             return ModeStatus { pending_input: true };
         }
 
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
         let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
         if self.output.text().is_empty() {
-            self.select.on_key(self.filter.visible_indices().len(), available_height, key);
+            self.select.on_key(self.filter.visible_indices().len(), available_height, count, key);
         } else {
-            self.output.on_key(available_height, key);
+            self.output.on_key(available_height, count, key);
         }
 
         let current_entry_index = self.filter.get_visible_index(self.select.cursor);
@@ -114,6 +141,11 @@ impl ModeTrait for Mode {
                     });
                 }
             }
+            Key::Char('o') => {
+                self.sort_by_time = !self.sort_by_time;
+                self.sort_entries();
+                self.filter.filter(self.entries.iter());
+            }
             Key::Char('D') => {
                 if let Some(current_entry_index) = current_entry_index {
                     self.state = State::Waiting(WaitOperation::Discard);
@@ -141,7 +173,10 @@ impl ModeTrait for Mode {
                 }
                 if let State::Idle = self.state {
                     match result {
-                        Ok(entries) => self.entries = entries,
+                        Ok(entries) => {
+                            self.entries = entries;
+                            self.sort_entries();
+                        }
                         Err(error) => self.output.set(error),
                     }
                 }
@@ -159,13 +194,19 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.select.on_resize(self.filter.visible_indices().len(), available_height);
+        self.output.on_resize(available_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
         let name = match self.state {
             State::Idle | State::Waiting(WaitOperation::Refresh) => "stash list",
             State::Waiting(WaitOperation::Discard) => "discard",
         };
 
-        let (left_help, right_help) = ("[p]pop [enter]details [D]discard", "[arrows]move [ctrl+f]filter");
+        let (left_help, right_help) = ("[p]pop [enter]details [D]discard", "[o]sort [arrows]move [ctrl+f]filter");
         (name, left_help, right_help)
     }
 
@@ -174,7 +215,8 @@ impl ModeTrait for Mode {
         if self.output.text.is_empty() {
             if self.entries.is_empty() {
                 if let State::Idle = self.state {
-                    drawer.output(&Output::new("No Stashes!".to_owned()));
+                    drawer
+                        .fmt(format_args!("{}no stashes yet! press [ctrl+s] in status to stash changes", Color::DarkYellow));
                 }
             } else {
                 drawer.select_menu(
@@ -1,14 +1,15 @@
-use std::thread;
+use std::{env, fs, thread};
 
 use crate::{
-    backend::BackendResult,
+    backend::{BackendResult, DiffOptions, RevisionEntry},
     mode::*,
     platform::Key,
-    ui::{Drawer, RESERVED_LINES_COUNT},
+    ui::{Color, Drawer, RESERVED_LINES_COUNT},
 };
 
 pub enum Response {
-    Refresh(BackendResult<String>),
+    Refresh(BackendResult<(String, Vec<RevisionEntry>)>),
+    PatchWritten(BackendResult<String>),
 }
 
 #[derive(Clone, Debug)]
@@ -25,10 +26,21 @@ impl Default for State {
 #[derive(Default, Clone, Debug)]
 pub struct Mode {
     state: State,
+    entries: Vec<RevisionEntry>,
     output: Output,
+    select: SelectMenu,
+    count: Count,
+    filter: Filter,
+    show_full_summary: bool,
+    message: String,
     stash_id: usize,
     from: ModeKind,
 }
+impl Mode {
+    fn get_selected_entries(&self) -> Vec<RevisionEntry> {
+        self.entries.iter().filter(|&e| e.selected).cloned().collect()
+    }
+}
 
 impl ModeTrait for Mode {
     fn on_enter(&mut self, ctx: &ModeContext, info: ModeChangeInfo) {
@@ -38,36 +50,92 @@ impl ModeTrait for Mode {
         self.state = State::Waiting;
 
         self.output.set(String::new());
+        self.message.clear();
+        self.filter.clear();
+        self.select.cursor = 0;
+        self.show_full_summary = false;
         self.from = info.from;
         self.stash_id = as_variant!(info.info.unwrap(), ModeInfo::StashDetails).unwrap();
 
         let stash_id = self.stash_id;
         let ctx = ctx.clone();
         thread::spawn(move || {
-            let result = ctx.backend.stash_show(stash_id);
+            let result = ctx.backend.stash_show(stash_id).and_then(|summary| {
+                let mut entries = ctx.backend.stash_files(stash_id)?;
+                entries.sort_unstable_by(|a, b| a.status.cmp(&b.status));
+                Ok((summary, entries))
+            });
             ctx.event_sender.send_response(ModeResponse::StashDetails(Response::Refresh(result)));
         });
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
+        if self.filter.has_focus() {
+            self.filter.on_key(key);
+            self.filter.filter(self.entries.iter());
+            self.select.saturate_cursor(self.filter.visible_indices().len());
+
+            return ModeStatus { pending_input: true };
+        }
+
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
         if let State::Idle = self.state {
-            if self.output.line_count() > 1 {
-                let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
-                self.output.on_key(available_height, key);
+            let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+            let line_count = if self.show_full_summary { self.output.line_count() } else { 1 };
+
+            match self.select.on_key(
+                self.filter.visible_indices().len(),
+                available_height.saturating_sub(line_count + 1),
+                count,
+                key,
+            ) {
+                SelectMenuAction::None => (),
+                SelectMenuAction::Toggle(i) => {
+                    if let Some(i) = self.filter.get_visible_index(i) {
+                        self.entries[i].selected = !self.entries[i].selected
+                    }
+                }
+                SelectMenuAction::ToggleAll => {
+                    let all_selected = self.filter.visible_indices().iter().all(|&i| self.entries[i].selected);
+                    for &i in self.filter.visible_indices() {
+                        self.entries[i].selected = !all_selected;
+                    }
+                }
             }
 
             match key {
+                Key::Ctrl('f') => self.filter.enter(),
+                Key::Tab => {
+                    self.show_full_summary = !self.show_full_summary;
+                }
                 Key::Enter => {
+                    let entries = self.get_selected_entries();
+                    let request = diff::DiffRequest::Stash(self.stash_id, entries);
+                    ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::StashDetails, request));
+                }
+                Key::Char('d') => {
+                    let entries = self.get_selected_entries();
+                    let request = diff::DiffRequest::StashWorkingTree(self.stash_id, entries);
+                    ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::StashDetails, request));
+                }
+                Key::Char('w') => {
+                    self.state = State::Waiting;
+
                     let stash_id = self.stash_id;
                     let ctx = ctx.clone();
                     thread::spawn(move || {
-                        ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::new(ModeKind::StashDetails));
-
-                        let output = match ctx.backend.stash_diff(stash_id) {
-                            Ok(info) => info,
-                            Err(error) => error,
-                        };
-                        ctx.event_sender.send_response(ModeResponse::Diff(diff::Response::Refresh(output)));
+                        let result = ctx.backend.stash_diff(stash_id, &[], &DiffOptions::default()).and_then(|patch| {
+                            let path =
+                                env::current_dir().map_err(|e| e.to_string())?.join(format!("stash-{}.patch", stash_id));
+                            match fs::write(&path, patch) {
+                                Ok(()) => Ok(format!("patch written to {}", path.display())),
+                                Err(error) => Err(format!("could not write patch to {}: {}", path.display(), error)),
+                            }
+                        });
+                        ctx.event_sender.send_response(ModeResponse::StashDetails(Response::PatchWritten(result)));
                     });
                 }
                 _ => (),
@@ -84,14 +152,28 @@ impl ModeTrait for Mode {
                 if let State::Waiting = self.state {
                     self.state = State::Idle;
                 }
-                let info = match result {
-                    Ok(info) => info,
+                match result {
+                    Ok((summary, entries)) => {
+                        self.output.set(summary);
+                        self.entries = entries;
+                    }
+                    Err(error) => {
+                        self.output.set(error);
+                        self.entries.clear();
+                    }
+                }
+
+                self.filter.filter(self.entries.iter());
+                self.select.saturate_cursor(self.filter.visible_indices().len());
+            }
+            Response::PatchWritten(result) => {
+                if let State::Waiting = self.state {
+                    self.state = State::Idle;
+                }
+                self.message = match result {
+                    Ok(message) => message,
                     Err(error) => error,
                 };
-                // if info.is_empty() {
-                //     info.push('\n');
-                // }
-                self.output.set(info);
             }
         }
     }
@@ -103,11 +185,52 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        let line_count = if self.show_full_summary { self.output.line_count() } else { 1 };
+        self.select.on_resize(self.filter.visible_indices().len(), available_height.saturating_sub(line_count + 1));
+        self.output.on_resize(available_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
-        ("stash details", "[enter]diff", "[Left]back [arrows]move")
+        (
+            "stash details",
+            "[enter]diff [d]diff working tree against it [w]write patch",
+            "[tab]full summary [Left]back [arrows]move [space]toggle [a]toggle all [ctrl+f]filter",
+        )
     }
 
     fn draw(&self, drawer: &mut Drawer) {
-        drawer.stash_details(&self.output);
+        let mut line_count = drawer.filter(&self.filter);
+
+        if !self.message.is_empty() {
+            drawer.fmt(format_args!("{}{}{}", Color::DarkYellow, &self.message, Color::White));
+            drawer.next_line();
+            line_count += 1;
+        }
+
+        line_count += if self.show_full_summary {
+            drawer.stash_details(&self.output)
+        } else {
+            // the last line of 'git stash show' is the 'n files changed' summary
+            let output = self.output.text().lines().last().unwrap_or("").trim();
+            let output = match output.char_indices().nth(drawer.viewport_size.0.saturating_sub(1) as _) {
+                Some((i, c)) => &output[..i + c.len_utf8()],
+                None => output,
+            };
+            drawer.str(output);
+            drawer.next_line();
+            1
+        };
+
+        if let State::Idle = self.state {
+            drawer.next_line();
+            drawer.select_menu(
+                &self.select,
+                (line_count + 1).min(u16::MAX as _) as _,
+                false,
+                self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
+            );
+        }
     }
 }
@@ -1,27 +1,48 @@
-use std::thread;
+use std::{collections::HashSet, thread};
 
 use crate::{
-    backend::{Backend, BackendResult, FileStatus, RevisionEntry, StatusInfo},
+    backend::{Backend, BackendResult, DiffOptions, FileStatus, PendingOperation, RevisionEntry, StatusInfo},
     mode::*,
-    platform::Key,
+    platform::{Key, Platform},
     ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
 };
 
 pub enum Response {
     Idle,
+    Staged,
     Refresh(StatusInfo),
+    Entries(Vec<RevisionEntry>), // parsed so far while refreshing, in no particular order
+    Header(String),
     Commit(String),
+    CommitAll(String),
+    CommitEmpty(String),
+    CommitRejected(String, String), // (message, error)
+    CommitStaged(String),
+    CommitDate(String),
+    CopyPatch(BackendResult<()>),
+    AmendPushed(BackendResult<bool>),
     Stash(String),
+    ApplyPatch(String),
+    Move(String),
 }
 
 #[derive(Clone, Debug)]
 enum WaitOperation {
     Refresh,
     Commit,
+    Stage,
+    Unstage,
+    PendingOperation,
     Discard,
+    Remove,
+    Move,
     Stash,
     ResolveTakingOurs,
     ResolveTakingTheirs,
+    Rerere,
+    ApplyPatch,
+    CopyPatch,
+    Undo,
 }
 
 #[derive(Clone, Debug)]
@@ -56,11 +77,92 @@ impl SelectEntryDraw for RevisionEntry {
             trimmed_name,
             width = FileStatus::max_len(),
         ));
+        if self.conflict_count > 0 {
+            drawer.fmt(format_args!(" {}({} conflicts){}", Color::DarkRed, self.conflict_count, Color::White));
+        } else if self.rerere_resolved {
+            drawer.fmt(format_args!(" {}(recorded resolution){}", Color::DarkGreen, Color::White));
+        }
 
         1
     }
 }
 
+// directory of a status entry path, empty for the repository root
+fn directory(path: &str) -> &str {
+    // untracked directories are listed with a trailing slash
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+// whether a failed commit is worth retrying with another message, as it is when a hook rejects it,
+// as opposed to git refusing it or failing outright
+fn is_worth_retrying_commit(error: &str) -> bool {
+    const REFUSALS: &[&str] =
+        &["nothing to commit", "nothing added to commit", "no changes added to commit", "Please tell me who you are"];
+    const FAILURES: &[&str] = &["fatal:", "error: gpg failed"];
+    !REFUSALS.iter().any(|refusal| error.contains(refusal))
+        && !error.lines().any(|line| FAILURES.iter().any(|failure| line.starts_with(failure)))
+}
+
+// stashing with nothing selected stashes every tracked change but leaves untracked files alone
+fn stash_preview(entries: &[RevisionEntry]) -> String {
+    let selected: Vec<_> = entries.iter().filter(|e| e.selected).collect();
+    let (mut preview, stashed) = if selected.is_empty() {
+        let tracked = entries.iter().filter(|e| e.status != FileStatus::Untracked).collect();
+        ("nothing is selected, so every tracked change gets stashed:".to_string(), tracked)
+    } else {
+        (format!("stashing the {} selected file(s):", selected.len()), selected)
+    };
+
+    let mut names = HashSet::new();
+    for entry in stashed {
+        // staged and unstaged changes to the same file are listed once
+        if names.insert(&entry.name) {
+            preview.push_str(&format!("\n  {:>width$} {}", entry.status.as_str(), entry.name, width = FileStatus::max_len()));
+        }
+    }
+    preview
+}
+
+// a line of the status list when grouping by directory
+#[derive(Clone, Debug)]
+enum Row {
+    Directory(String, usize), // directory, entry count
+    Entry(usize),             // index into entries
+}
+
+struct RowView<'a> {
+    row: &'a Row,
+    entries: &'a [RevisionEntry],
+    collapsed: bool,
+}
+impl SelectEntryDraw for RowView<'_> {
+    fn draw(&self, drawer: &mut Drawer, hovered: bool, full: bool) -> usize {
+        match self.row {
+            Row::Directory(directory, count) => {
+                let fold = if self.collapsed { '+' } else { '-' };
+                let directory = if directory.is_empty() { "." } else { directory };
+                drawer.fmt(format_args!(
+                    "{}{} {}/ ({}){}",
+                    if hovered { Color::White } else { Color::DarkYellow },
+                    fold,
+                    directory,
+                    count,
+                    Color::White
+                ));
+                1
+            }
+            Row::Entry(i) => {
+                drawer.str("  ");
+                self.entries[*i].draw(drawer, hovered, full)
+            }
+        }
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Mode {
     state: State,
@@ -69,25 +171,136 @@ pub struct Mode {
     select: SelectMenu,
     filter: Filter,
     from: ModeKind,
+    undo: Option<String>,
+    status_filter: Option<FileStatus>,
+    confirm_remove: bool,
+    confirm_amend: bool,
+    confirm_abort: bool,
+    move_from: Option<String>,
+    group_by_directory: bool,
+    collapsed_directories: HashSet<String>,
+    rows: Vec<Row>,
+    // set after staging for review so committing with nothing selected keeps the rest unstaged
+    staged: bool,
+    pending_operation: Option<PendingOperation>,
+    commit_date: Option<String>, // asked for before the message, dropped on the next key
+    more_keys: bool,             // the header shows the less common keys instead of the core ones
 }
 impl Mode {
+    fn filter_entries(&mut self) {
+        let status_filter = &self.status_filter;
+        self.filter.filter_by(self.entries.iter(), |e| status_filter.iter().all(|s| e.status == *s));
+        self.group_entries();
+    }
+
+    fn group_entries(&mut self) {
+        self.rows.clear();
+        if !self.group_by_directory {
+            return;
+        }
+
+        // stable sort so entries keep their order inside a directory
+        let entries = &self.entries;
+        let mut indices = self.filter.visible_indices().to_vec();
+        indices.sort_by_key(|&i| directory(&entries[i].name));
+
+        let mut directory_row = 0;
+        for i in indices {
+            let entry_directory = directory(&entries[i].name);
+            match self.rows.get_mut(directory_row) {
+                Some(Row::Directory(d, count)) if d == entry_directory => *count += 1,
+                _ => {
+                    directory_row = self.rows.len();
+                    self.rows.push(Row::Directory(entry_directory.into(), 1));
+                }
+            }
+            if !self.collapsed_directories.contains(entry_directory) {
+                self.rows.push(Row::Entry(i));
+            }
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        if self.group_by_directory {
+            self.rows.len()
+        } else {
+            self.filter.visible_indices().len()
+        }
+    }
+
+    // entries shown at a list position, every entry inside it for a directory
+    fn row_entries(&self, position: usize) -> Vec<usize> {
+        if !self.group_by_directory {
+            return self.filter.get_visible_index(position).into_iter().collect();
+        }
+        match self.rows.get(position) {
+            Some(Row::Directory(d, _)) => {
+                self.filter.visible_indices().iter().copied().filter(|&i| directory(&self.entries[i].name) == d).collect()
+            }
+            Some(&Row::Entry(i)) => vec![i],
+            None => Vec::new(),
+        }
+    }
+
+    fn hovered_entry(&self) -> Option<usize> {
+        if !self.group_by_directory {
+            return self.filter.get_visible_index(self.select.cursor);
+        }
+        match self.rows.get(self.select.cursor) {
+            Some(&Row::Entry(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    fn toggle_hovered_directory(&mut self) {
+        let hovered_directory = match self.rows.get(self.select.cursor) {
+            Some(Row::Directory(d, _)) => d.clone(),
+            Some(&Row::Entry(i)) => directory(&self.entries[i].name).into(),
+            None => return,
+        };
+        if !self.collapsed_directories.remove(&hovered_directory) {
+            self.collapsed_directories.insert(hovered_directory.clone());
+        }
+        self.group_entries();
+
+        // keep the cursor on the directory that was just folded
+        if let Some(position) = self.rows.iter().position(|r| matches!(r, Row::Directory(d, _) if *d == hovered_directory)) {
+            self.select.cursor = position;
+        }
+    }
+
     fn get_selected_entries(&self) -> Vec<RevisionEntry> {
         let entries: Vec<_> = self.entries.iter().filter(|&e| e.selected).cloned().collect();
         entries
     }
 
+    // unlike the other operations, nothing selected means just the hovered entry
+    fn get_selected_or_hovered_entries(&self) -> Vec<RevisionEntry> {
+        let entries = self.get_selected_entries();
+        if !entries.is_empty() {
+            return entries;
+        }
+        self.row_entries(self.select.cursor).into_iter().map(|i| self.entries[i].clone()).collect()
+    }
+
+    fn remove(&mut self, ctx: &ModeContext, keep_on_disk: bool) {
+        let entries = self.get_selected_or_hovered_entries();
+        if entries.is_empty() {
+            return;
+        }
+        self.state = State::Waiting(WaitOperation::Remove);
+        request(ctx, move |b| b.remove(&entries, keep_on_disk));
+    }
+
     fn remove_selected_entries(&mut self) {
         let previous_len = self.entries.len();
 
         for i in (0..self.entries.len()).rev() {
             if self.entries[i].selected {
                 self.entries.remove(i);
-                self.filter.on_remove_entry(i);
-                let i = match self.filter.visible_indices().binary_search(&i) {
-                    Ok(i) => i,
-                    Err(i) => i,
-                };
-                self.select.on_remove_entry(i);
+                if let Some(i) = self.filter.on_remove_entry(i) {
+                    self.select.on_remove_entry(i);
+                }
             }
         }
 
@@ -96,29 +309,118 @@ impl Mode {
             self.select.cursor = 0;
             self.filter.clear();
         }
+
+        self.group_entries();
+        if self.group_by_directory {
+            self.select.saturate_cursor(self.rows.len());
+        }
+    }
+
+    // what to stage before committing, 'None' commits only what is already staged
+    fn entries_to_commit(&mut self) -> Option<Vec<RevisionEntry>> {
+        let entries = self.get_selected_entries();
+        let staged = std::mem::take(&mut self.staged);
+        if staged && entries.is_empty() {
+            None
+        } else {
+            Some(entries)
+        }
     }
 
     fn commit<S: Into<String>>(&mut self, ctx: &ModeContext, message: S, amend: bool) {
         self.state = State::Waiting(WaitOperation::Commit);
 
-        let entries = self.get_selected_entries();
+        let entries = self.entries_to_commit();
         self.remove_selected_entries();
 
         let message = message.into();
         //log(format!("amend: {}, commit message: \n {:?}, entries: {:?}\n", amend, message, entries));
 
+        // kept until the next key so a rejected commit is retried with the same date
+        let date = self.commit_date.clone();
         let ctx = ctx.clone();
-        thread::spawn(move || match ctx.backend.commit(&message, &entries, amend) {
+        thread::spawn(move || {
+            let failed = |error| {
+                let info = StatusInfo { header: error, ..Default::default() };
+                ctx.event_sender.send_response(ModeResponse::Status(Response::Refresh(info)));
+            };
+
+            // staged on its own so a failure there is not mistaken for the commit being rejected
+            if let Some(entries) = &entries {
+                if let Err(error) = ctx.backend.stage(entries) {
+                    failed(error);
+                    return;
+                }
+            }
+
+            match ctx.backend.commit(&message, None, amend, date.as_deref()) {
+                Ok(()) => {
+                    log(format!("commit ok\n"));
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::Idle));
+                    ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Status));
+                }
+                // likely a hook rejecting it, the files are staged by now so the message is all that is needed to retry
+                Err(error) if !amend && !message.is_empty() && is_worth_retrying_commit(&error) => {
+                    let mut info = ctx.backend.status().unwrap_or_default();
+                    info.header = error.clone();
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::Refresh(info)));
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::CommitRejected(message, error)));
+                }
+                Err(error) => failed(error),
+            }
+        });
+    }
+
+    fn stage_and_review<F>(&mut self, ctx: &ModeContext, f: F)
+    where
+        F: 'static + Send + FnOnce(&dyn Backend) -> BackendResult<()>,
+    {
+        self.state = State::Waiting(WaitOperation::Stage);
+
+        let ctx = ctx.clone();
+        thread::spawn(move || match f(ctx.backend.as_ref()) {
+            Ok(()) => {
+                ctx.event_sender.send_response(ModeResponse::Status(Response::Staged));
+                ctx.event_sender
+                    .send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::Status, diff::DiffRequest::Staged));
+            }
+            Err(error) => ctx
+                .event_sender
+                .send_response(ModeResponse::Status(Response::Refresh(StatusInfo { header: error, ..Default::default() }))),
+        });
+    }
+
+    fn commit_empty(&mut self, ctx: &ModeContext, message: String) {
+        self.state = State::Waiting(WaitOperation::Commit);
+
+        let ctx = ctx.clone();
+        thread::spawn(move || match ctx.backend.commit_empty(&message) {
             Ok(()) => {
-                log(format!("commit ok\n"));
                 ctx.event_sender.send_response(ModeResponse::Status(Response::Idle));
                 ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Status));
             }
             Err(error) => ctx
                 .event_sender
-                .send_response(ModeResponse::Status(Response::Refresh(StatusInfo { header: error, entries: Vec::new() }))),
+                .send_response(ModeResponse::Status(Response::Refresh(StatusInfo { header: error, ..Default::default() }))),
         });
     }
+
+    fn commit_in_editor(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Commit);
+
+        let entries = self.entries_to_commit();
+        let verbose = ctx.config.commit_verbose;
+
+        // runs on this thread as nothing should be drawn while the editor owns the terminal
+        match Platform::suspend(|| ctx.backend.commit_in_editor(entries.as_deref(), verbose)) {
+            Ok(()) => {
+                self.remove_selected_entries();
+                ctx.event_sender.send_response(ModeResponse::Status(Response::Idle));
+                ctx.event_sender.send_mode_change(ModeKind::Log, ModeChangeInfo::new(ModeKind::Status));
+            }
+            Err(error) => request(ctx, move |_| Err(error)),
+        }
+    }
 }
 
 impl ModeTrait for Mode {
@@ -129,31 +431,48 @@ impl ModeTrait for Mode {
         self.state = State::Waiting(WaitOperation::Refresh);
 
         self.output.set(String::new());
-        self.filter.filter(self.entries.iter());
-        self.select.saturate_cursor(self.filter.visible_indices().len());
+        self.filter_entries();
+        self.select.saturate_cursor(self.row_count());
         self.from = info.from;
 
+        // nothing to keep on screen, so entries are shown as soon as they come
+        if self.entries.is_empty() {
+            request_incremental(ctx);
+        } else {
+            request(ctx, |_| Ok(()));
+        }
+    }
+
+    fn is_showing_output(&self) -> bool {
+        self.output.line_count() > 1
+    }
+
+    fn request_refresh(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Refresh);
         request(ctx, |_| Ok(()));
     }
 
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
         if self.filter.has_focus() {
             self.filter.on_key(key);
-            self.filter.filter(self.entries.iter());
-            self.select.saturate_cursor(self.filter.visible_indices().len());
+            self.filter_entries();
+            self.select.saturate_cursor(self.row_count());
 
             return ModeStatus { pending_input: true };
         }
 
         let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        // digits filter by status here, so there is no repeat count
         if self.output.line_count() > 1 {
-            self.output.on_key(available_height, key);
+            self.output.on_key(available_height, 1, key);
         } else {
-            match self.select.on_key(self.filter.visible_indices().len(), available_height.saturating_sub(2), key) {
+            match self.select.on_key(self.row_count(), available_height.saturating_sub(2), 1, key) {
                 SelectMenuAction::None => (),
-                SelectMenuAction::Toggle(i) => {
-                    if let Some(i) = self.filter.get_visible_index(i) {
-                        self.entries[i].selected = !self.entries[i].selected
+                SelectMenuAction::Toggle(position) => {
+                    let indices = self.row_entries(position);
+                    let all_selected = indices.iter().all(|&i| self.entries[i].selected);
+                    for i in indices {
+                        self.entries[i].selected = !all_selected;
                     }
                 }
                 SelectMenuAction::ToggleAll => {
@@ -165,6 +484,10 @@ impl ModeTrait for Mode {
             }
         }
 
+        self.commit_date = None;
+        let confirm_remove = std::mem::take(&mut self.confirm_remove);
+        let confirm_amend = std::mem::take(&mut self.confirm_amend);
+        let confirm_abort = std::mem::take(&mut self.confirm_abort);
         match key {
             Key::Ctrl('f') => self.filter.enter(),
             Key::Char('c') => {
@@ -176,24 +499,100 @@ impl ModeTrait for Mode {
                     };
                     ctx.event_sender.send_mode_change(
                         ModeKind::MessageInput,
-                        ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit),
+                        ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit).commit_message(),
+                    );
+                } else if let State::Idle = self.state {
+                    let not_empty = true;
+                    let placeholder = "type in the empty commit message...";
+                    let on_submit = |ctx: &ModeContext, message: String| {
+                        ctx.event_sender.send_response(ModeResponse::Status(Response::CommitEmpty(message)));
+                    };
+                    ctx.event_sender.send_mode_change(
+                        ModeKind::MessageInput,
+                        ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit).commit_message(),
                     );
                 }
             }
-            Key::Char('A') => {
-                if !self.entries.is_empty() {
+            Key::Ctrl('a') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                let not_empty = true;
+                let placeholder = "type in the commit message for all changes...";
+                let on_submit = |ctx: &ModeContext, message: String| {
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::CommitAll(message)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit).commit_message(),
+                );
+            }
+            Key::Char('B') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                let not_empty = true;
+                let placeholder = "type in the commit date (e.g. '2024-01-31 12:00' or 'yesterday')...";
+                let on_submit = |ctx: &ModeContext, date: String| {
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::CommitDate(date)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit),
+                );
+            }
+            Key::Char('C') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                self.commit_in_editor(ctx);
+            }
+            // amending what is already pushed rewrites shared history so it asks for the key again
+            Key::Char('A') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                if confirm_amend || ctx.config.expert_mode {
                     self.commit(ctx, "", true);
+                } else {
+                    self.state = State::Waiting(WaitOperation::Commit);
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let result = ctx.backend.head_is_pushed();
+                        ctx.event_sender.send_response(ModeResponse::Status(Response::AmendPushed(result)));
+                    });
                 }
             }
             Key::Char('D') => {
                 if matches!(self.state, State::Idle) && !self.entries.is_empty() {
                     self.state = State::Waiting(WaitOperation::Discard);
+                    self.staged = false;
                     let entries = self.get_selected_entries();
                     self.remove_selected_entries();
 
                     request(ctx, move |b| b.discard(&entries));
                 }
             }
+            // removing from disk can not be undone so it asks for the key again
+            Key::Char('X') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                if confirm_remove || ctx.config.expert_mode {
+                    self.remove(ctx, false);
+                } else {
+                    self.confirm_remove = true;
+                }
+            }
+            Key::Char('U') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                self.remove(ctx, true);
+            }
+            Key::Char('M') if matches!(self.state, State::Idle) => {
+                if let Some(i) = self.hovered_entry() {
+                    let path = self.entries[i].name.clone();
+                    self.move_from = Some(path.clone());
+
+                    let not_empty = true;
+                    let placeholder = "type in the new path...";
+                    let on_submit = |ctx: &ModeContext, path: String| {
+                        ctx.event_sender.send_response(ModeResponse::Status(Response::Move(path)));
+                    };
+                    ctx.event_sender.send_mode_change(
+                        ModeKind::MessageInput,
+                        ModeChangeInfo::message_input_with_text(ModeKind::Status, not_empty, placeholder, path, on_submit),
+                    );
+                }
+            }
+            Key::Char('u') if matches!(self.state, State::Idle) && self.undo.is_some() => {
+                self.undo = None;
+                self.state = State::Waiting(WaitOperation::Undo);
+                request(ctx, Backend::undo);
+            }
             Key::Char('O') => {
                 if matches!(self.state, State::Idle) && !self.entries.is_empty() {
                     self.state = State::Waiting(WaitOperation::ResolveTakingOurs);
@@ -219,26 +618,146 @@ impl ModeTrait for Mode {
                     };
                     ctx.event_sender.send_mode_change(
                         ModeKind::MessageInput,
-                        ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit),
+                        ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit)
+                            .preview(stash_preview(&self.entries)),
                     );
                 }
             }
+            Key::Char('I') => {
+                let not_empty = true;
+                let placeholder = "type in the patch file path...";
+                let on_submit = |ctx: &ModeContext, path: String| {
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::ApplyPatch(path)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit),
+                );
+            }
+            Key::Char('v') => {
+                if let Some(i) = self.hovered_entry() {
+                    let path = self.entries[i].name.clone();
+                    ctx.event_sender
+                        .send_mode_change(ModeKind::FileView, ModeChangeInfo::file(ModeKind::Status, String::new(), path));
+                }
+            }
+            // previews the ours side first, the file view switches to theirs
+            Key::Char('V') => {
+                if let Some(i) = self.hovered_entry() {
+                    if let FileStatus::Unmerged = self.entries[i].status {
+                        let path = self.entries[i].name.clone();
+                        ctx.event_sender
+                            .send_mode_change(ModeKind::FileView, ModeChangeInfo::file(ModeKind::Status, ":2".into(), path));
+                    }
+                }
+            }
+            // untracked files have no diff so they are left out of the patch
+            Key::Char('y') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                self.state = State::Waiting(WaitOperation::CopyPatch);
+                let entries = self.get_selected_entries();
+                let ctx = ctx.clone();
+                thread::spawn(move || {
+                    let result = ctx.backend.diff(None, &entries, &DiffOptions::default()).and_then(|patch| {
+                        if patch.is_empty() {
+                            Err("nothing to copy".into())
+                        } else {
+                            Platform::set_clipboard(&patch)
+                        }
+                    });
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::CopyPatch(result)));
+                });
+            }
             Key::Enter => {
                 if !self.entries.is_empty() {
                     let entries = self.get_selected_entries();
 
-                    let ctx = ctx.clone();
-                    thread::spawn(move || {
-                        ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::new(ModeKind::Status));
+                    ctx.event_sender.send_mode_change(
+                        ModeKind::Diff,
+                        ModeChangeInfo::diff(ModeKind::Status, diff::DiffRequest::Changes(entries)),
+                    );
+                }
+            }
+            Key::Char(c @ '1'..='5') => {
+                let status = match c {
+                    '1' => FileStatus::Modified,
+                    '2' => FileStatus::Untracked,
+                    '3' => FileStatus::Added,
+                    '4' => FileStatus::Deleted,
+                    _ => FileStatus::Unmerged,
+                };
+                // pressing the same key again shows every status back
+                self.status_filter = if self.status_filter.as_ref() == Some(&status) { None } else { Some(status) };
+                self.filter_entries();
+                self.select.saturate_cursor(self.row_count());
+            }
+            Key::Char('i') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                let entries = self.get_selected_entries();
+                self.stage_and_review(ctx, move |b| b.stage(&entries));
+            }
+            Key::Char('e') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                self.stage_and_review(ctx, Backend::stage_tracked);
+            }
+            Key::Char('r') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                self.state = State::Waiting(WaitOperation::Unstage);
+                let entries = self.get_selected_entries();
+                if entries.is_empty() {
+                    self.staged = false;
+                }
 
-                        let output = match ctx.backend.diff(None, &entries) {
-                            Ok(output) => output,
-                            Err(error) => error,
-                        };
-                        ctx.event_sender.send_response(ModeResponse::Diff(diff::Response::Refresh(output)));
-                    });
+                request(ctx, move |b| b.unstage(&entries));
+            }
+            Key::Char('E') if matches!(self.state, State::Idle) => {
+                self.state = State::Waiting(WaitOperation::Rerere);
+                request(ctx, Backend::rerere);
+            }
+            Key::Char('F') if matches!(self.state, State::Idle) => {
+                let entries: Vec<_> = self
+                    .get_selected_or_hovered_entries()
+                    .into_iter()
+                    .filter(|e| matches!(e.status, FileStatus::Unmerged))
+                    .collect();
+                if !entries.is_empty() {
+                    self.state = State::Waiting(WaitOperation::Rerere);
+                    request(ctx, move |b| b.rerere_forget(&entries));
+                }
+            }
+            Key::Char('K') if matches!(self.state, State::Idle) => {
+                if let Some(operation) = self.pending_operation {
+                    self.state = State::Waiting(WaitOperation::PendingOperation);
+                    request(ctx, move |b| b.continue_operation(operation));
                 }
             }
+            // aborting throws away every conflict resolved so far so it asks for the key again
+            Key::Char('Q') if matches!(self.state, State::Idle) => {
+                if let Some(operation) = self.pending_operation {
+                    if confirm_abort || ctx.config.expert_mode {
+                        self.state = State::Waiting(WaitOperation::PendingOperation);
+                        request(ctx, move |b| b.abort_operation(operation));
+                    } else {
+                        self.confirm_abort = true;
+                    }
+                }
+            }
+            Key::Char('?') => self.more_keys = !self.more_keys,
+            Key::Char('g') => {
+                self.group_by_directory = !self.group_by_directory;
+                self.group_entries();
+                self.select.cursor = 0;
+                self.select.scroll = 0;
+            }
+            Key::Tab if self.group_by_directory => {
+                self.toggle_hovered_directory();
+                self.select.on_resize(self.row_count(), available_height.saturating_sub(2));
+            }
+            Key::Char('R') if !self.entries.is_empty() => {
+                let request = diff::DiffRequest::Renames(None, self.get_selected_entries());
+                ctx.event_sender.send_mode_change(ModeKind::Diff, ModeChangeInfo::diff(ModeKind::Status, request));
+            }
+            // nothing selected means the hunks of every file
+            Key::Char('p') if matches!(self.state, State::Idle) && !self.entries.is_empty() => {
+                let info = ModeChangeInfo::hunks(ModeKind::Status, self.get_selected_entries());
+                ctx.event_sender.send_mode_change(ModeKind::Hunks, info);
+            }
             _ => (),
         }
 
@@ -248,31 +767,129 @@ impl ModeTrait for Mode {
     fn on_response(&mut self, ctx: &ModeContext, response: ModeResponse) {
         let response = as_variant!(response, ModeResponse::Status).unwrap();
         match response {
-            Response::Refresh(info) => {
-                if let State::Waiting(_) = self.state {
+            Response::Refresh(mut info) => {
+                if let State::Waiting(operation) = &self.state {
+                    self.undo = match operation {
+                        WaitOperation::Discard => ctx.backend.undo_description(),
+                        WaitOperation::Refresh => self.undo.take(),
+                        _ => None,
+                    };
                     self.state = State::Idle;
                 }
                 if let State::Idle = self.state {
                     self.output.set(info.header);
                 }
+                self.pending_operation = info.pending_operation;
 
+                // files removed by the operation are already gone, so the ones left keep their selection
+                let selected: HashSet<_> = self.entries.iter().filter(|e| e.selected).map(|e| &e.name).collect();
+                for entry in &mut info.entries {
+                    entry.selected = selected.contains(&entry.name);
+                }
                 self.entries = info.entries;
 
-                self.filter.filter(self.entries.iter());
-                self.select.saturate_cursor(self.filter.visible_indices().len());
+                self.filter_entries();
+                self.select.saturate_cursor(self.row_count());
+            }
+            Response::Entries(entries) => {
+                if let State::Waiting(WaitOperation::Refresh) = self.state {
+                    self.entries.extend(entries);
+                    self.filter_entries();
+                    self.select.saturate_cursor(self.row_count());
+                }
+            }
+            Response::Header(header) => {
+                if let State::Idle = self.state {
+                    self.output.set(header);
+                }
             }
             Response::Commit(message) => self.commit(ctx, message, false),
+            Response::CommitAll(message) => {
+                // no selected entries means the backend stages everything
+                for entry in &mut self.entries {
+                    entry.selected = false;
+                }
+                self.staged = false;
+                self.commit(ctx, message, false);
+            }
+            Response::CommitEmpty(message) => self.commit_empty(ctx, message),
+            Response::CommitDate(date) => {
+                self.commit_date = Some(date);
+                let not_empty = true;
+                let placeholder = "type in the backdated commit message...";
+                let on_submit = |ctx: &ModeContext, message: String| {
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::Commit(message)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Status, not_empty, placeholder, on_submit).commit_message(),
+                );
+            }
+            Response::CommitRejected(message, error) => {
+                let not_empty = true;
+                let placeholder = "type in the commit message...";
+                let on_submit = |ctx: &ModeContext, message: String| {
+                    ctx.event_sender.send_response(ModeResponse::Status(Response::CommitStaged(message)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input_with_text(ModeKind::Status, not_empty, placeholder, message, on_submit)
+                        .commit_message()
+                        .error(error),
+                );
+            }
+            Response::AmendPushed(result) => {
+                self.state = State::Idle;
+                match result {
+                    Ok(true) => self.confirm_amend = true,
+                    Ok(false) => self.commit(ctx, "", true),
+                    Err(error) => self.output.set(error),
+                }
+            }
+            Response::CopyPatch(result) => {
+                self.state = State::Idle;
+                ctx.event_sender.send_notice(match result {
+                    Ok(()) => "copied patch to clipboard".into(),
+                    Err(error) => error.lines().last().unwrap_or("").into(),
+                });
+            }
+            Response::CommitStaged(message) => {
+                for entry in &mut self.entries {
+                    entry.selected = false;
+                }
+                self.staged = true;
+                self.commit(ctx, message, false);
+            }
             Response::Stash(message) => {
                 self.state = State::Waiting(WaitOperation::Stash);
 
                 let entries = self.get_selected_entries();
                 self.remove_selected_entries();
 
-                request(ctx, move |b| b.stash(&message, &entries));
+                // selected untracked files are stashed as well, as the preview promised
+                let include_untracked = entries.iter().any(|e| e.status == FileStatus::Untracked);
+                request(ctx, move |b| b.stash(&message, &entries, include_untracked));
+            }
+            Response::ApplyPatch(path) => {
+                self.state = State::Waiting(WaitOperation::ApplyPatch);
+                request(ctx, move |b| b.apply_patch(&path));
+            }
+            Response::Move(to) => {
+                if let Some(from) = self.move_from.take() {
+                    self.state = State::Waiting(WaitOperation::Move);
+                    request(ctx, move |b| b.move_file(&from, &to));
+                }
             }
             Response::Idle => {
                 self.state = State::Idle;
             }
+            Response::Staged => {
+                self.state = State::Idle;
+                self.staged = true;
+                for entry in &mut self.entries {
+                    entry.selected = false;
+                }
+            }
         }
     }
 
@@ -283,19 +900,50 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.output.on_resize(available_height);
+        self.select.on_resize(self.row_count(), available_height.saturating_sub(2));
+    }
+
     fn header(&self) -> (&str, &str, &str) {
         let name = match self.state {
             State::Idle | State::Waiting(WaitOperation::Refresh) => "status",
             State::Waiting(WaitOperation::Commit) => "commit",
+            State::Waiting(WaitOperation::Stage) => "stage",
+            State::Waiting(WaitOperation::Unstage) => "unstage",
+            State::Waiting(WaitOperation::PendingOperation) => match self.pending_operation {
+                Some(operation) => operation.name(),
+                None => "continue",
+            },
             State::Waiting(WaitOperation::Stash) => "stash",
             State::Waiting(WaitOperation::Discard) => "discard",
+            State::Waiting(WaitOperation::Remove) => "remove",
+            State::Waiting(WaitOperation::Move) => "move",
             State::Waiting(WaitOperation::ResolveTakingOurs) => "resolve taking ours",
             State::Waiting(WaitOperation::ResolveTakingTheirs) => "resolve taking theirs",
+            State::Waiting(WaitOperation::Rerere) => "rerere",
+            State::Waiting(WaitOperation::ApplyPatch) => "apply patch",
+            State::Waiting(WaitOperation::CopyPatch) => "copy patch",
+            State::Waiting(WaitOperation::Undo) => "undo",
+        };
+        let hovered_unmerged = self.hovered_entry().is_some_and(|i| matches!(self.entries[i].status, FileStatus::Unmerged));
+        let (left_help, right_help) = if self.more_keys {
+            (
+                "[ctrl+a]commit all [B]backdated commit [C]commit in editor [e]stage tracked and review [?]core keys",
+                "[ctrl+s]stash [X]remove [U]untrack [M]move [R]diff renames [I]apply patch [y]copy patch [1-5]status filter [g]group by directory [tab]fold",
+            )
+        } else if hovered_unmerged {
+            (
+                "[O]take ours [T]take theirs [V]preview ours/theirs [E]reapply recorded [F]forget recorded [?]more keys",
+                "[enter]diff [v]view file [space]toggle [a]toggle all [arrows]move [ctrl+f]filter",
+            )
+        } else {
+            (
+                "[c]commit [A]amend [i]stage and review [p]commit hunks [r]unstage [D]discard [?]more keys",
+                "[enter]diff [v]view file [space]toggle [a]toggle all [arrows]move [ctrl+f]filter",
+            )
         };
-        let (left_help, right_help) = (
-            "[c]commit [A]amend [D]discard [ctrl+s]stash [enter]diff [O]take ours [T]take theirs",
-            "[arrows]move [space]toggle [a]toggle all [ctrl+f]filter",
-        );
         (name, left_help, right_help)
     }
 
@@ -315,17 +963,70 @@ impl ModeTrait for Mode {
 
             drawer.str(output);
             drawer.next_line();
+            let notice_start = drawer.buf_len();
+            if self.confirm_remove {
+                let count = self.get_selected_or_hovered_entries().len();
+                drawer.fmt(format_args!(
+                    "{}press [X] again to remove {} file(s) from disk{}",
+                    Color::DarkYellow,
+                    count,
+                    Color::White
+                ));
+            } else if self.confirm_amend {
+                drawer.fmt(format_args!(
+                    "{}the last commit is already pushed, press [A] again to amend it anyway{}",
+                    Color::DarkYellow,
+                    Color::White
+                ));
+            } else if let Some(operation) = self.pending_operation {
+                let keys = match operation {
+                    PendingOperation::Bisect if self.confirm_abort => "press [Q] again to reset",
+                    PendingOperation::Bisect => "press [Q] to reset",
+                    _ if self.confirm_abort => "press [Q] again to abort, losing the conflicts resolved so far",
+                    _ => "press [K] to continue or [Q] to abort",
+                };
+                drawer.background(Color::DarkRed);
+                drawer.fmt(format_args!(" {} in progress, {} ", operation.name(), keys));
+                drawer.background(Color::Black);
+            } else if self.staged {
+                drawer.fmt(format_args!(
+                    "{}staged changes are committed alone unless files are selected{}",
+                    Color::DarkYellow,
+                    Color::White
+                ));
+            } else if let Some(undo) = &self.undo {
+                drawer.fmt(format_args!("{}press [u] to undo: {}{}", Color::DarkYellow, undo, Color::White));
+            }
+            if let Some(status) = &self.status_filter {
+                if drawer.buf_len() > notice_start {
+                    drawer.str(" | ");
+                }
+                drawer.fmt(format_args!("{}showing only {} files{}", Color::DarkYellow, status.as_str(), Color::White));
+            }
             drawer.next_line();
-            drawer.select_menu(
-                &self.select,
-                2 + filter_line_count,
-                false,
-                self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
-            );
+            if self.group_by_directory {
+                let rows: Vec<_> = self
+                    .rows
+                    .iter()
+                    .map(|row| RowView {
+                        row,
+                        entries: &self.entries,
+                        collapsed: matches!(row, Row::Directory(d, _) if self.collapsed_directories.contains(d)),
+                    })
+                    .collect();
+                drawer.select_menu(&self.select, 2 + filter_line_count, false, rows.iter());
+            } else {
+                drawer.select_menu(
+                    &self.select,
+                    2 + filter_line_count,
+                    false,
+                    self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
+                );
+            }
 
             if self.entries.is_empty() {
                 let empty_message = match self.state {
-                    State::Idle => "nothing to commit!",
+                    State::Idle => "nothing to commit! press [c] to create an empty commit",
                     _ => "working...",
                 };
                 drawer.fmt(format_args!("{}{}", Color::DarkYellow, empty_message));
@@ -344,10 +1045,405 @@ where
 
         let mut info = match f(ctx.backend.deref()).and_then(|_| ctx.backend.status()) {
             Ok(info) => info,
-            Err(error) => StatusInfo { header: error, entries: Vec::new() },
+            Err(error) => StatusInfo { header: error, ..Default::default() },
         };
         info.entries.sort_unstable_by(|a, b| a.status.cmp(&b.status));
 
         ctx.event_sender.send_response(ModeResponse::Status(Response::Refresh(info)));
     });
 }
+
+fn request_incremental(ctx: &ModeContext) {
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let mut on_entries = |entries| {
+            ctx.event_sender.send_response(ModeResponse::Status(Response::Entries(entries)));
+        };
+        let mut info = match ctx.backend.status_incremental(&mut on_entries) {
+            Ok(info) => info,
+            Err(error) => StatusInfo { header: error, ..Default::default() },
+        };
+        // sorting waits until everything is listed
+        info.entries.sort_unstable_by(|a, b| a.status.cmp(&b.status));
+
+        ctx.event_sender.send_response(ModeResponse::Status(Response::Refresh(info)));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+
+    fn fake_backend() -> Arc<FakeBackend> {
+        let status_entries = vec![
+            RevisionEntry::new("a".into(), FileStatus::Modified),
+            RevisionEntry::new("b".into(), FileStatus::Added),
+            RevisionEntry::new("c".into(), FileStatus::Deleted),
+            RevisionEntry::new("d".into(), FileStatus::Renamed),
+        ];
+        Arc::new(FakeBackend { status_header: "on branch master".into(), status_entries, ..Default::default() })
+    }
+
+    fn entered_mode(ctx: &ModeContext, receiver: &crate::application::EventReceiver) -> Mode {
+        let mut mode = Mode::default();
+        mode.on_enter(ctx, ModeChangeInfo::new(ModeKind::Status));
+        while mode.is_waiting_response() {
+            mode.on_response(ctx, receiver.recv_response().unwrap());
+        }
+        mode
+    }
+
+    fn entry_names(mode: &Mode) -> Vec<&str> {
+        mode.entries.iter().map(|e| &e.name[..]).collect()
+    }
+
+    #[test]
+    fn refresh_lists_status_entries() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mode = entered_mode(&ctx, &receiver);
+
+        assert!(matches!(mode.state, State::Idle));
+        assert_eq!(mode.output.text(), "on branch master");
+        assert_eq!(entry_names(&mode), ["a", "b", "c", "d"]);
+        assert_eq!(mode.filter.visible_indices(), [0, 1, 2, 3]);
+        assert_eq!(backend.calls(), ["status"]);
+    }
+
+    #[test]
+    fn discard_removes_selected_entries() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        for key in [Key::Down, Key::Char(' '), Key::Down, Key::Down, Key::Char(' ')] {
+            mode.on_key(&ctx, key);
+        }
+        assert_eq!(mode.select.cursor, 3);
+
+        mode.on_key(&ctx, Key::Char('D'));
+        assert_eq!(entry_names(&mode), ["a", "c"]);
+        assert_eq!(mode.filter.visible_indices(), [0, 1]);
+        assert_eq!(mode.select.cursor, 1);
+
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "discard", "status"]);
+    }
+
+    #[test]
+    fn discard_hidden_entry_keeps_filtered_entries() {
+        let status_entries = vec![
+            RevisionEntry::new("src/a".into(), FileStatus::Modified),
+            RevisionEntry::new("b".into(), FileStatus::Added),
+            RevisionEntry::new("src/c".into(), FileStatus::Deleted),
+            RevisionEntry::new("d".into(), FileStatus::Renamed),
+        ];
+        let backend = Arc::new(FakeBackend { status_entries, ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend);
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        for key in [Key::Down, Key::Char(' '), Key::Ctrl('f'), Key::Char('s'), Key::Char('r'), Key::Char('c'), Key::Enter] {
+            mode.on_key(&ctx, key);
+        }
+        mode.on_key(&ctx, Key::Down);
+        assert_eq!(mode.filter.visible_indices(), [0, 2]);
+        assert_eq!(mode.select.cursor, 1);
+
+        mode.on_key(&ctx, Key::Char('D'));
+        assert_eq!(entry_names(&mode), ["src/a", "src/c", "d"]);
+        assert_eq!(mode.filter.visible_indices(), [0, 1]);
+        assert_eq!(mode.select.cursor, 1);
+    }
+
+    #[test]
+    fn status_filter_toggles() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend);
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char('4'));
+        assert_eq!(mode.filter.visible_indices(), [2]);
+
+        mode.on_key(&ctx, Key::Char('3'));
+        assert_eq!(mode.filter.visible_indices(), [1]);
+
+        mode.on_key(&ctx, Key::Char('3'));
+        assert_eq!(mode.filter.visible_indices(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn refresh_keeps_selection() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        for key in [Key::Down, Key::Char(' '), Key::Down, Key::Down, Key::Char(' '), Key::Char('O')] {
+            mode.on_key(&ctx, key);
+        }
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+
+        assert_eq!(backend.calls(), ["status", "resolve_taking_ours", "status"]);
+        let selected: Vec<_> = mode.entries.iter().filter(|e| e.selected).map(|e| &e.name[..]).collect();
+        assert_eq!(selected, ["b", "d"]);
+    }
+
+    #[test]
+    fn remove_asks_for_confirmation() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char('X'));
+        assert!(mode.confirm_remove);
+        mode.on_key(&ctx, Key::Down);
+        assert!(!mode.confirm_remove);
+        assert_eq!(backend.calls(), ["status"]);
+
+        mode.on_key(&ctx, Key::Char('X'));
+        mode.on_key(&ctx, Key::Char('X'));
+        assert!(matches!(mode.state, State::Waiting(WaitOperation::Remove)));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "remove", "status"]);
+
+        mode.on_key(&ctx, Key::Char('U'));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "remove", "status", "remove", "status"]);
+    }
+
+    #[test]
+    fn remove_skips_confirmation_in_expert_mode() {
+        let backend = fake_backend();
+        let (mut ctx, receiver) = ModeContext::for_test(backend.clone());
+        ctx.config = Arc::new(crate::config::Config { expert_mode: true, ..Default::default() });
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char('X'));
+        assert!(matches!(mode.state, State::Waiting(WaitOperation::Remove)));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "remove", "status"]);
+    }
+
+    #[test]
+    fn amend_asks_for_confirmation_when_pushed() {
+        let status_entries = vec![RevisionEntry::new("a".into(), FileStatus::Modified)];
+        let backend = Arc::new(FakeBackend { status_entries, head_pushed: true, ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char('A'));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert!(mode.confirm_amend);
+        mode.on_key(&ctx, Key::Char('A'));
+        assert!(!mode.confirm_amend);
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "head_is_pushed", "stage", "commit"]);
+    }
+
+    #[test]
+    fn move_renames_hovered_entry() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Down);
+        mode.on_key(&ctx, Key::Char('M'));
+        assert_eq!(mode.move_from.as_deref(), Some("b"));
+
+        mode.on_response(&ctx, ModeResponse::Status(Response::Move("e".into())));
+        assert!(matches!(mode.state, State::Waiting(WaitOperation::Move)));
+        assert!(mode.move_from.is_none());
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "move_file", "status"]);
+    }
+
+    #[test]
+    fn group_by_directory_folds() {
+        let status_entries = vec![
+            RevisionEntry::new("src/a".into(), FileStatus::Modified),
+            RevisionEntry::new("b".into(), FileStatus::Added),
+            RevisionEntry::new("src/c".into(), FileStatus::Deleted),
+        ];
+        let backend = Arc::new(FakeBackend { status_entries, ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend);
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char('g'));
+        assert_eq!(mode.row_count(), 5);
+        assert!(matches!(&mode.rows[2], Row::Directory(d, 2) if d == "src"));
+
+        // toggling a directory toggles every entry inside it
+        for key in [Key::Down, Key::Down, Key::Char(' ')] {
+            mode.on_key(&ctx, key);
+        }
+        let selected: Vec<_> = mode.entries.iter().filter(|e| e.selected).map(|e| &e.name[..]).collect();
+        assert_eq!(selected, ["src/a", "src/c"]);
+
+        mode.on_key(&ctx, Key::Down);
+        mode.on_key(&ctx, Key::Tab);
+        assert_eq!(mode.row_count(), 3);
+        assert_eq!(mode.select.cursor, 2);
+        assert_eq!(mode.hovered_entry(), None);
+
+        mode.on_key(&ctx, Key::Tab);
+        assert_eq!(mode.row_count(), 5);
+    }
+
+    #[test]
+    fn stage_then_commit_keeps_the_rest_unstaged() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char(' '));
+        mode.on_key(&ctx, Key::Char('i'));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert!(mode.staged);
+        assert!(mode.entries.iter().all(|e| !e.selected));
+
+        assert!(mode.entries_to_commit().is_none());
+        assert!(!mode.staged);
+        assert!(mode.entries_to_commit().is_some());
+        assert_eq!(backend.calls(), ["status", "stage"]);
+    }
+
+    #[test]
+    fn stage_tracked_ignores_the_selection() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char(' '));
+        mode.on_key(&ctx, Key::Char('e'));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert!(mode.staged);
+        assert!(mode.entries.iter().all(|e| !e.selected));
+        assert_eq!(backend.calls(), ["status", "stage_tracked"]);
+    }
+
+    #[test]
+    fn continue_keys_only_while_an_operation_is_pending() {
+        let backend = fake_backend();
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_key(&ctx, Key::Char('K'));
+        assert!(matches!(mode.state, State::Idle));
+
+        let backend = Arc::new(FakeBackend { pending_operation: Some(PendingOperation::Rebase), ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+        assert_eq!(mode.pending_operation, Some(PendingOperation::Rebase));
+        assert_eq!(mode.header().0, "status");
+
+        mode.on_key(&ctx, Key::Char('K'));
+        assert_eq!(mode.header().0, "rebase");
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        mode.on_key(&ctx, Key::Char('Q'));
+        assert!(mode.confirm_abort);
+        mode.on_key(&ctx, Key::Char('Q'));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(backend.calls(), ["status", "continue_operation", "status", "abort_operation", "status"]);
+    }
+
+    #[test]
+    fn entries_are_listed_before_status_finishes() {
+        let status_entries =
+            vec![RevisionEntry::new("x".into(), FileStatus::Untracked), RevisionEntry::new("y".into(), FileStatus::Modified)];
+        let backend = Arc::new(FakeBackend { status_entries, ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend);
+        let mut mode = Mode::default();
+        mode.on_enter(&ctx, ModeChangeInfo::new(ModeKind::Status));
+
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert!(mode.is_waiting_response());
+        assert_eq!(entry_names(&mode), ["x", "y"]);
+
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert!(!mode.is_waiting_response());
+        assert_eq!(entry_names(&mode), ["y", "x"]);
+    }
+
+    #[test]
+    fn backend_error_is_shown_in_output() {
+        let backend = Arc::new(FakeBackend { error: Some("not a repository".into()), ..Default::default() });
+        let (ctx, receiver) = ModeContext::for_test(backend);
+        let mode = entered_mode(&ctx, &receiver);
+
+        assert_eq!(mode.output.text(), "not a repository");
+        assert!(mode.entries.is_empty());
+    }
+
+    #[test]
+    fn rejected_commit_keeps_the_message() {
+        let backend = Arc::new(FakeBackend {
+            error: Some("rejected by hook".into()),
+            failing_call: Some("commit"),
+            ..Default::default()
+        });
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_response(&ctx, ModeResponse::Status(Response::Commit("wip".into())));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(mode.output.text(), "rejected by hook");
+        match receiver.recv_response() {
+            Some(ModeResponse::Status(Response::CommitRejected(message, error))) => {
+                assert_eq!(message, "wip");
+                assert_eq!(error, "rejected by hook");
+            }
+            _ => panic!("expected the rejected commit"),
+        }
+
+        // the retry commits what the rejected one already staged
+        mode.on_response(&ctx, ModeResponse::Status(Response::CommitStaged("wip, fixed".into())));
+        assert!(!mode.staged);
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert!(matches!(receiver.recv_response(), Some(ModeResponse::Status(Response::CommitRejected(..)))));
+        assert_eq!(backend.calls(), ["status", "stage", "commit", "status", "commit", "status"]);
+    }
+
+    #[test]
+    fn failed_staging_does_not_ask_for_the_message_again() {
+        let backend = Arc::new(FakeBackend {
+            error: Some("index.lock exists".into()),
+            failing_call: Some("stage"),
+            ..Default::default()
+        });
+        let (ctx, receiver) = ModeContext::for_test(backend.clone());
+        let mut mode = entered_mode(&ctx, &receiver);
+
+        mode.on_response(&ctx, ModeResponse::Status(Response::Commit("wip".into())));
+        mode.on_response(&ctx, receiver.recv_response().unwrap());
+        assert_eq!(mode.output.text(), "index.lock exists");
+        assert_eq!(backend.calls(), ["status", "stage"]);
+
+        assert!(is_worth_retrying_commit("pre-commit: trailing whitespace in a"));
+        assert!(!is_worth_retrying_commit("git commit -m wip:\nnothing to commit, working tree clean\n"));
+        assert!(!is_worth_retrying_commit("git commit -m wip:\n\n*** Please tell me who you are."));
+        assert!(!is_worth_retrying_commit(
+            "git commit -m wip:\nerror: gpg failed to sign the data\nfatal: failed to write commit object\n"
+        ));
+        assert!(!is_worth_retrying_commit("git commit -m wip:\nfatal: Unable to create '.git/index.lock': File exists.\n"));
+    }
+
+    #[test]
+    fn stash_preview_lists_what_gets_stashed() {
+        let mut entries = vec![
+            RevisionEntry::new("a".into(), FileStatus::Modified),
+            RevisionEntry::new("a".into(), FileStatus::Modified),
+            RevisionEntry::new("b".into(), FileStatus::Untracked),
+            RevisionEntry::new("c".into(), FileStatus::Deleted),
+        ];
+        let preview = stash_preview(&entries);
+        assert!(preview.starts_with("nothing is selected"));
+        assert_eq!(preview.lines().count(), 3);
+        assert!(!preview.contains(" b"));
+
+        entries[2].selected = true;
+        let preview = stash_preview(&entries);
+        assert_eq!(preview.lines().collect::<Vec<_>>(), ["stashing the 1 selected file(s):", "  untracked b"]);
+    }
+}
@@ -1,16 +1,20 @@
 use std::thread;
 
 use crate::{
-    backend::{Backend, BackendResult, TagEntry},
+    backend::{Backend, BackendResult, LogEntry, TagEntry},
     mode::*,
     platform::Key,
-    ui::{Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
+    ui::{Color, Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
 };
 
 pub enum Response {
     Refresh(BackendResult<Vec<TagEntry>>),
     Checkout,
     New(String),
+    SignedName(String),
+    NewSigned(String), // message
+    Verify(String),
+    Changelog(String),
 }
 
 #[derive(Clone, Debug)]
@@ -18,6 +22,8 @@ enum WaitOperation {
     Refresh,
     New,
     Delete,
+    Verify,
+    Changelog,
 }
 
 #[derive(Clone, Debug)]
@@ -31,20 +37,43 @@ impl Default for State {
     }
 }
 
-impl SelectEntryDraw for TagEntry {
+struct TagEntryView<'a> {
+    entry: &'a TagEntry,
+    marked: bool,
+}
+impl SelectEntryDraw for TagEntryView<'_> {
     fn draw(&self, drawer: &mut Drawer, _: bool, _: bool) -> usize {
-        drawer.str(&self.name);
+        drawer.str(&self.entry.name);
+        if self.marked {
+            drawer.fmt(format_args!(" {}(marked){}", Color::DarkYellow, Color::White));
+        }
         1
     }
 }
 
+fn format_changelog(from: &str, to: &str, entries: &[LogEntry]) -> String {
+    if entries.is_empty() {
+        return format!("no commits between {} and {}", from, to);
+    }
+
+    let mut changelog = format!("{} commit(s) between {} and {}\n", entries.len(), from, to);
+    for entry in entries {
+        let message = entry.message.lines().next().unwrap_or("");
+        changelog.push_str(&format!("\n{} {} {} {}", entry.hash, entry.date, entry.author, message));
+    }
+    changelog
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Mode {
     state: State,
     entries: Vec<TagEntry>,
     output: Output,
     select: SelectMenu,
+    count: Count,
     filter: Filter,
+    marked: Option<String>,      // tag name the changelog starts from
+    signed_name: Option<String>, // asked for before the message of a signed tag
 }
 impl ModeTrait for Mode {
     fn on_enter(&mut self, ctx: &ModeContext, _info: ModeChangeInfo) {
@@ -60,6 +89,15 @@ impl ModeTrait for Mode {
         request(ctx, |_| Ok(()));
     }
 
+    fn is_showing_output(&self) -> bool {
+        !self.output.text().is_empty()
+    }
+
+    fn request_refresh(&mut self, ctx: &ModeContext) {
+        self.state = State::Waiting(WaitOperation::Refresh);
+        request(ctx, |_| Ok(()));
+    }
+
     fn on_key(&mut self, ctx: &ModeContext, key: Key) -> ModeStatus {
         if self.filter.has_focus() {
             self.filter.on_key(key);
@@ -69,11 +107,15 @@ impl ModeTrait for Mode {
             return ModeStatus { pending_input: true };
         }
 
+        if self.count.on_key(key) {
+            return ModeStatus { pending_input: false };
+        }
+        let count = self.count.take();
         let available_height = (ctx.viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
         if self.output.text().is_empty() {
-            self.select.on_key(self.filter.visible_indices().len(), available_height, key);
+            self.select.on_key(self.filter.visible_indices().len(), available_height, count, key);
         } else {
-            self.output.on_key(available_height, key);
+            self.output.on_key(available_height, count, key);
         }
 
         let current_entry_index = self.filter.get_visible_index(self.select.cursor);
@@ -104,12 +146,70 @@ impl ModeTrait for Mode {
                     ModeChangeInfo::message_input(ModeKind::Branches, not_empty, placeholder, on_submit),
                 );
             }
+            Key::Char('N') => {
+                let not_empty = true;
+                let placeholder = "type in the signed tag name...";
+                let on_submit = |ctx: &ModeContext, name: String| {
+                    ctx.event_sender.send_response(ModeResponse::Tags(Response::SignedName(name)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Tags, not_empty, placeholder, on_submit),
+                );
+            }
+            // toggles between the tag list and the verification or changelog output
+            Key::Char('v') | Key::Char('c') if !self.output.text().is_empty() => self.output.set(String::new()),
+            Key::Char('v') => {
+                if let Some(current_entry_index) = current_entry_index {
+                    self.state = State::Waiting(WaitOperation::Verify);
+
+                    let name = self.entries[current_entry_index].name.clone();
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let output = match ctx.backend.verify_tag(&name) {
+                            Ok(output) => output,
+                            Err(error) => error,
+                        };
+                        ctx.event_sender.send_response(ModeResponse::Tags(Response::Verify(output)));
+                    });
+                }
+            }
+            Key::Char('m') => {
+                if let Some(current_entry_index) = current_entry_index {
+                    let name = &self.entries[current_entry_index].name;
+                    self.marked = match &self.marked {
+                        Some(marked) if marked == name => None,
+                        _ => Some(name.clone()),
+                    };
+                }
+            }
+            Key::Char('c') => {
+                if let (Some(current_entry_index), Some(from)) = (current_entry_index, &self.marked) {
+                    self.state = State::Waiting(WaitOperation::Changelog);
+
+                    let from = from.clone();
+                    let to = self.entries[current_entry_index].name.clone();
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        let output = match ctx.backend.log_range(&from, &to) {
+                            Ok(entries) => format_changelog(&from, &to, &entries),
+                            Err(error) => error,
+                        };
+                        ctx.event_sender.send_response(ModeResponse::Tags(Response::Changelog(output)));
+                    });
+                } else if self.marked.is_none() {
+                    self.output.set("mark the tag the changelog starts from with [m] first".into());
+                }
+            }
             Key::Char('D') => {
                 if let Some(current_entry_index) = current_entry_index {
                     let entry = &self.entries[current_entry_index];
                     self.state = State::Waiting(WaitOperation::Delete);
 
                     let name = entry.name.clone();
+                    if self.marked.as_ref() == Some(&name) {
+                        self.marked = None;
+                    }
                     self.entries.remove(current_entry_index);
                     self.filter.on_remove_entry(current_entry_index);
                     self.select.on_remove_entry(self.select.cursor);
@@ -145,7 +245,30 @@ impl ModeTrait for Mode {
             Response::Checkout => self.state = State::Idle,
             Response::New(name) => {
                 self.state = State::Waiting(WaitOperation::New);
-                request(ctx, move |b| b.new_tag(&name));
+                request(ctx, move |b| b.new_tag(&name, None));
+            }
+            Response::SignedName(name) => {
+                self.signed_name = Some(name);
+
+                let not_empty = true;
+                let placeholder = "type in the signed tag message...";
+                let on_submit = |ctx: &ModeContext, message: String| {
+                    ctx.event_sender.send_response(ModeResponse::Tags(Response::NewSigned(message)));
+                };
+                ctx.event_sender.send_mode_change(
+                    ModeKind::MessageInput,
+                    ModeChangeInfo::message_input(ModeKind::Tags, not_empty, placeholder, on_submit),
+                );
+            }
+            Response::NewSigned(message) => {
+                if let Some(name) = self.signed_name.take() {
+                    self.state = State::Waiting(WaitOperation::New);
+                    request(ctx, move |b| b.new_tag(&name, Some(&message)));
+                }
+            }
+            Response::Verify(output) | Response::Changelog(output) => {
+                self.state = State::Idle;
+                self.output.set(output);
             }
         }
     }
@@ -157,25 +280,44 @@ impl ModeTrait for Mode {
         }
     }
 
+    fn on_resize(&mut self, size: (u16, u16)) {
+        let available_height = (size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        self.select.on_resize(self.filter.visible_indices().len(), available_height);
+        self.output.on_resize(available_height);
+    }
+
     fn header(&self) -> (&str, &str, &str) {
         let name = match self.state {
             State::Idle | State::Waiting(WaitOperation::Refresh) => "tags",
             State::Waiting(WaitOperation::New) => "new tag",
             State::Waiting(WaitOperation::Delete) => "delete tag",
+            State::Waiting(WaitOperation::Verify) => "verify tag",
+            State::Waiting(WaitOperation::Changelog) => "changelog",
         };
-        let (left_help, right_help) = ("[enter]checkout [n]new [D]delete", "[arrows]move [ctrl+f]filter");
+        let (left_help, right_help) = (
+            "[enter]checkout [n]new [N]new signed [v]verify [m]mark [c]changelog since marked [D]delete",
+            "[arrows]move [ctrl+f]filter",
+        );
         (name, left_help, right_help)
     }
 
     fn draw(&self, drawer: &mut Drawer) {
         let filter_line_count = drawer.filter(&self.filter);
         if self.output.text.is_empty() {
-            drawer.select_menu(
-                &self.select,
-                filter_line_count,
-                false,
-                self.filter.visible_indices().iter().map(|&i| &self.entries[i]),
-            );
+            if self.entries.is_empty() && matches!(self.state, State::Idle) {
+                drawer.fmt(format_args!("{}no tags yet! press [n] to create one", Color::DarkYellow));
+                return;
+            }
+            let entries: Vec<_> = self
+                .filter
+                .visible_indices()
+                .iter()
+                .map(|&i| {
+                    let entry = &self.entries[i];
+                    TagEntryView { entry, marked: self.marked.as_ref() == Some(&entry.name) }
+                })
+                .collect();
+            drawer.select_menu(&self.select, filter_line_count, false, entries.iter());
         } else {
             drawer.output(&self.output);
         }
@@ -1,3 +1,12 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::{Condvar, Mutex, OnceLock},
+};
+
+use crate::ui;
+
 #[cfg(unix)]
 use std::os::unix::io::RawFd;
 
@@ -59,7 +68,118 @@ impl Key {
     }
 
     pub fn is_back(&self) -> bool {
-        matches!(self, Key::Left)
+        matches!(self, Self::Left | Self::Esc)
+    }
+}
+
+// set while a child process owns the terminal so the event reader does not steal its input
+static SUSPENDED: Mutex<bool> = Mutex::new(false);
+static RESUMED: Condvar = Condvar::new();
+
+// returns true if it had to wait for the terminal to be resumed
+fn wait_while_suspended() -> bool {
+    let mut suspended = SUSPENDED.lock().unwrap();
+    let waited = *suspended;
+    while *suspended {
+        suspended = RESUMED.wait(suspended).unwrap();
+    }
+    waited
+}
+
+impl Platform {
+    // hands the terminal over to whatever 'f' spawns (an editor for example) and restores the tui after
+    pub fn suspend<T, F: FnOnce() -> T>(f: F) -> T {
+        *SUSPENDED.lock().unwrap() = true;
+        Self::write_codes(&[ui::RESET_STYLE_CODE, ui::SHOW_CURSOR_CODE, ui::EXIT_ALTERNATE_BUFFER_CODE]);
+        Self::set_raw_mode(false);
+
+        let result = f();
+
+        Self::set_raw_mode(true);
+        Self::write_codes(&[ui::ENTER_ALTERNATE_BUFFER_CODE, ui::HIDE_CURSOR_CODE]);
+        *SUSPENDED.lock().unwrap() = false;
+        RESUMED.notify_all();
+
+        result
+    }
+
+    // runs the user's shell in the current directory until it exits, meant to be called inside 'suspend'
+    pub fn run_shell() -> io::Result<()> {
+        #[cfg(unix)]
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".into());
+        #[cfg(windows)]
+        let shell = env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".into());
+
+        Command::new(shell).status()?;
+        Ok(())
+    }
+
+    // shows text in the user's pager until it exits, meant to be called inside 'suspend'
+    pub fn run_pager(text: &str) -> io::Result<()> {
+        #[cfg(unix)]
+        let pager = env::var("PAGER").unwrap_or_else(|_| "less".into());
+        #[cfg(windows)]
+        let pager = env::var("PAGER").unwrap_or_else(|_| "more".into());
+
+        // PAGER may come with arguments, like 'less -R'
+        let mut args = pager.split_whitespace();
+        let command = args.next().unwrap_or("less");
+        let mut child = Command::new(command).args(args).stdin(Stdio::piped()).spawn()?;
+
+        // quitting the pager early closes the pipe, that is not an error
+        if let Some(mut stdin) = child.stdin.take() {
+            match stdin.write_all(text.as_bytes()) {
+                Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error),
+                _ => (),
+            }
+        }
+        child.wait()?;
+        Ok(())
+    }
+
+    // copies text to the system clipboard through the first clipboard tool found
+    pub fn set_clipboard(text: &str) -> Result<(), String> {
+        #[cfg(windows)]
+        const COMMANDS: &[(&str, &[&str])] = &[("clip", &[])];
+        #[cfg(target_os = "macos")]
+        const COMMANDS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+        #[cfg(all(unix, not(target_os = "macos")))]
+        const COMMANDS: &[(&str, &[&str])] =
+            &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])];
+
+        for (command, args) in COMMANDS {
+            let mut child = match Command::new(command)
+                .args(*args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                Ok(child) => child,
+                Err(_) => continue,
+            };
+
+            // dropping stdin closes it so the tool knows the text is complete
+            let written = match child.stdin.take() {
+                Some(mut stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+                None => false,
+            };
+            return match child.wait() {
+                Ok(status) if written && status.success() => Ok(()),
+                _ => Err(format!("could not copy to clipboard with '{}'", command)),
+            };
+        }
+
+        Err("no clipboard tool found".into())
+    }
+
+    fn write_codes(codes: &[&[u8]]) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        for code in codes {
+            stdout.write_all(code).unwrap();
+        }
+        stdout.flush().unwrap();
     }
 }
 
@@ -70,6 +190,10 @@ pub struct Platform {
     original: libc::termios,
 }
 
+// (original, raw)
+#[cfg(unix)]
+static TERMINAL_MODES: OnceLock<(libc::termios, libc::termios)> = OnceLock::new();
+
 #[cfg(unix)]
 impl Platform {
     pub fn new() -> Option<(Self, PlatformEventReader)> {
@@ -98,6 +222,7 @@ impl Platform {
             new.c_cc[libc::VMIN] = 0;
             new.c_cc[libc::VTIME] = 0;
             libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &new);
+            let _ = TERMINAL_MODES.set((original, new));
             original
         };
         let backspace_code = original.c_cc[libc::VERASE];
@@ -114,6 +239,13 @@ impl Platform {
 
         (size.ws_col as _, size.ws_row as _)
     }
+
+    fn set_raw_mode(raw: bool) {
+        if let Some((original, new)) = TERMINAL_MODES.get() {
+            let mode = if raw { new } else { original };
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, mode) };
+        }
+    }
 }
 
 #[cfg(unix)]
@@ -291,6 +423,7 @@ impl PlatformEventReader {
 
         for event_index in epoll_wait(self.queue_fd, &mut epoll_events) {
             match event_index {
+                0 if wait_while_suspended() => (),
                 0 => match Self::read(libc::STDIN_FILENO, &mut self.buf) {
                     Ok(0) | Err(()) => panic!("could not read from stdin"),
                     Ok(len) => Self::parse_terminal_keys(&self.buf[..len], self.backspace_code, keys),
@@ -390,6 +523,7 @@ impl PlatformEventReader {
 
         for event in kqueue_wait(self.queue_fd, &mut kqueue_events) {
             match event {
+                Ok(TriggeredEvent { index: 0, .. }) if wait_while_suspended() => (),
                 Ok(TriggeredEvent { index: 0, data }) => {
                     self.buf.resize(data as _, 0);
                     match Self::read(libc::STDIN_FILENO, &mut self.buf) {
@@ -413,6 +547,10 @@ pub struct Platform {
     output_handle_original_mode: DWORD,
 }
 
+// (input original, output original, input raw, output raw)
+#[cfg(windows)]
+static CONSOLE_MODES: OnceLock<(DWORD, DWORD, DWORD, DWORD)> = OnceLock::new();
+
 #[cfg(windows)]
 impl Platform {
     pub fn new() -> Option<(Self, PlatformEventReader)> {
@@ -424,9 +562,16 @@ impl Platform {
             return None;
         }
 
-        let input_handle_original_mode = Self::swap_console_mode(input_handle, ENABLE_WINDOW_INPUT);
-        let output_handle_original_mode =
-            Self::swap_console_mode(output_handle, ENABLE_PROCESSED_OUTPUT | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+        let input_handle_mode = ENABLE_WINDOW_INPUT;
+        let output_handle_mode = ENABLE_PROCESSED_OUTPUT | ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+        let input_handle_original_mode = Self::swap_console_mode(input_handle, input_handle_mode);
+        let output_handle_original_mode = Self::swap_console_mode(output_handle, output_handle_mode);
+        let _ = CONSOLE_MODES.set((
+            input_handle_original_mode,
+            output_handle_original_mode,
+            input_handle_mode,
+            output_handle_mode,
+        ));
 
         Some((Self { input_handle_original_mode, output_handle_original_mode }, PlatformEventReader))
     }
@@ -461,6 +606,18 @@ impl Platform {
         }
     }
 
+    fn set_raw_mode(raw: bool) {
+        if let Some(&(input_original, output_original, input_raw, output_raw)) = CONSOLE_MODES.get() {
+            let (input_mode, output_mode) = if raw { (input_raw, output_raw) } else { (input_original, output_original) };
+            if let Some(handle) = Self::get_std_handle(STD_INPUT_HANDLE) {
+                Self::set_console_mode(handle, input_mode);
+            }
+            if let Some(handle) = Self::get_std_handle(STD_OUTPUT_HANDLE) {
+                Self::set_console_mode(handle, output_mode);
+            }
+        }
+    }
+
     fn swap_console_mode(handle: HANDLE, new_mode: DWORD) -> DWORD {
         let mut original_mode = 0;
         let result = unsafe { GetConsoleMode(handle, &mut original_mode) };
@@ -497,6 +654,11 @@ impl PlatformEventReader {
             None => return,
         };
 
+        // best effort as a read that already started while suspending will still consume input
+        if wait_while_suspended() {
+            return;
+        }
+
         let mut events = [unsafe { std::mem::zeroed() }; 32];
         let mut event_count = 0;
         let result = unsafe { ReadConsoleInputW(input_handle, events.as_mut_ptr(), events.len() as _, &mut event_count) };
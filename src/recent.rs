@@ -0,0 +1,113 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    mode::SelectMenu,
+    platform::{Key, Platform, PlatformEventReader},
+    ui::{Drawer, SelectEntryDraw, RESERVED_LINES_COUNT},
+};
+
+const MAX_RECENT_REPOSITORIES: usize = 16;
+
+fn recent_repositories_path() -> Option<PathBuf> {
+    let data_dir = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else {
+        match env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("share"),
+        }
+    };
+    Some(data_dir.join("verco").join("recent_repositories"))
+}
+
+// most recent first, skipping the ones that no longer exist
+pub fn load() -> Vec<PathBuf> {
+    let path = match recent_repositories_path() {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    match fs::read_to_string(path) {
+        Ok(text) => text.lines().map(PathBuf::from).filter(|p| p.is_dir()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn add(root: &Path) {
+    let path = match recent_repositories_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let root = root.canonicalize().unwrap_or_else(|_| root.into());
+
+    let mut repositories = load();
+    repositories.retain(|r| r != &root);
+    repositories.insert(0, root);
+    repositories.truncate(MAX_RECENT_REPOSITORIES);
+
+    let mut text = String::new();
+    for repository in &repositories {
+        // a path with a line break can not be stored one per line
+        if let Some(repository) = repository.to_str().filter(|r| !r.contains('\n')) {
+            text.push_str(repository);
+            text.push('\n');
+        }
+    }
+
+    // remembering recent repositories is a convenience, failing to do so is fine
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, text);
+}
+
+impl SelectEntryDraw for PathBuf {
+    fn draw(&self, drawer: &mut Drawer, _: bool, _: bool) -> usize {
+        drawer.str(&self.to_string_lossy());
+        1
+    }
+}
+
+// lets the user pick one of the repositories, returns None if cancelled
+pub fn pick(event_reader: &mut PlatformEventReader, repositories: &[PathBuf]) -> Option<PathBuf> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut stdout_buf = Vec::new();
+
+    let mut viewport_size = Platform::terminal_size();
+    let mut select = SelectMenu::default();
+    let mut keys = Vec::new();
+
+    loop {
+        let mut drawer = Drawer::new(stdout_buf, viewport_size);
+        drawer.header("recent repositories", "[enter]open", "[arrows]move [esc]quit", b' ');
+        drawer.select_menu(&select, 0, false, repositories.iter());
+        drawer.clear_to_bottom();
+        stdout_buf = drawer.take_buf();
+        stdout.write_all(&stdout_buf).unwrap();
+        stdout.flush().unwrap();
+
+        let mut resize = None;
+        keys.clear();
+        event_reader.read_terminal_events(&mut keys, &mut resize);
+
+        if let Some(size) = resize {
+            viewport_size = size;
+        }
+        let available_height = (viewport_size.1 as usize).saturating_sub(RESERVED_LINES_COUNT);
+        select.on_resize(repositories.len(), available_height);
+
+        for &key in &keys {
+            match key {
+                Key::Esc | Key::Ctrl('c') | Key::Char('q') => return None,
+                Key::Enter => return repositories.get(select.cursor).cloned(),
+                _ => {
+                    select.on_key(repositories.len(), available_height, 1, key);
+                }
+            }
+        }
+    }
+}
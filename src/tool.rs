@@ -1,20 +1,82 @@
-use std::{fs, io::Write};
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-const LOG_TO_FILE_ENABLE: bool = false;
-const LOG_FILE_NAME: &str = "test.txt";
+const DEFAULT_LOG_FILE_NAME: &str = "verco.log";
+
+static LOG_TO_FILE_ENABLE: AtomicBool = AtomicBool::new(false);
+static LOG_FILE_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 pub fn log_init() {
-    if LOG_TO_FILE_ENABLE {
-        if let Err(err) = fs::remove_file(LOG_FILE_NAME) {
+    let enable = matches!(env::var("VERCO_LOG"), Ok(value) if !value.is_empty() && value != "0");
+    if !enable {
+        return;
+    }
+
+    let file_name = env::var("VERCO_LOG_FILE").unwrap_or_else(|_| DEFAULT_LOG_FILE_NAME.into());
+    // resolve it now since the current dir changes to the repository root later
+    let path = match env::current_dir() {
+        Ok(current_dir) => current_dir.join(file_name),
+        Err(_) => file_name.into(),
+    };
+
+    if let Err(err) = fs::remove_file(&path) {
+        if err.kind() != std::io::ErrorKind::NotFound {
             println!("log file delete: {}", err);
         }
     }
+
+    let _ = LOG_FILE_PATH.set(path);
+    LOG_TO_FILE_ENABLE.store(true, Ordering::Relaxed);
 }
 
 pub fn log<S: Into<String>>(info: S) {
-    if LOG_TO_FILE_ENABLE {
-        let mut file =
-            fs::OpenOptions::new().write(true).append(true).create(true).open(LOG_FILE_NAME).expect("log file open failed!");
-        file.write_all(info.into().as_bytes()).unwrap();
+    if !LOG_TO_FILE_ENABLE.load(Ordering::Relaxed) {
+        return;
+    }
+    let path = match LOG_FILE_PATH.get() {
+        Some(path) => path,
+        None => return,
+    };
+
+    // utc time of day, there's no need to pull a date library just for the log
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() % (24 * 60 * 60);
+    let prefix = format!(
+        "[{:02}:{:02}:{:02}.{:03} {:?}] ",
+        seconds / (60 * 60),
+        (seconds / 60) % 60,
+        seconds % 60,
+        since_epoch.subsec_millis(),
+        thread::current().id(),
+    );
+
+    let mut file = fs::OpenOptions::new().append(true).create(true).open(path).expect("log file open failed!");
+    file.write_all(prefix.as_bytes()).unwrap();
+    file.write_all(info.into().as_bytes()).unwrap();
+}
+
+// '2h ago' like text from a unix timestamp in seconds
+pub fn relative_time(timestamp: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const WEEK: i64 = 7 * DAY;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    match (now - timestamp).max(0) {
+        seconds if seconds < MINUTE => format!("{}s ago", seconds),
+        seconds if seconds < HOUR => format!("{}m ago", seconds / MINUTE),
+        seconds if seconds < DAY => format!("{}h ago", seconds / HOUR),
+        seconds if seconds < WEEK => format!("{}d ago", seconds / DAY),
+        seconds => format!("{}w ago", seconds / WEEK),
     }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::mode::{Filter, Output, ReadLine, SelectMenu};
+use crate::mode::{Filter, Output, ReadLine, SelectMenu, Toggles};
 
 pub const HEADER_LINE_COUNT: usize = 2;
 pub const RESERVED_LINES_COUNT: usize = HEADER_LINE_COUNT + 1;
@@ -42,6 +42,27 @@ pub fn set_foreground_color(buf: &mut Vec<u8>, color: Color) {
     buf.push(b'm');
 }
 
+// writes a help text like `[c]commit [A]amend` coloring the bracketed keys differently from their labels
+fn help_text(buf: &mut Vec<u8>, help: &[u8], key_color: Color, label_color: Color) {
+    let mut in_key = false;
+    for &b in help {
+        match b {
+            b'[' if !in_key => {
+                in_key = true;
+                set_foreground_color(buf, key_color);
+                buf.push(b);
+            }
+            b']' if in_key => {
+                in_key = false;
+                buf.push(b);
+                set_foreground_color(buf, label_color);
+            }
+            _ => buf.push(b),
+        }
+    }
+    set_foreground_color(buf, label_color);
+}
+
 #[derive(Clone, Copy)]
 pub enum Color {
     Black,
@@ -66,6 +87,19 @@ impl Color {
             Self::White => "15",
         }
     }
+
+    // deterministic color for a text so the same author always gets the same color
+    pub fn from_text(text: &str) -> Self {
+        const COLORS: [Color; 5] = [Color::DarkRed, Color::DarkGreen, Color::DarkYellow, Color::DarkBlue, Color::DarkMagenta];
+
+        // fnv-1a as std's hashers are not guaranteed to be stable
+        let mut hash: u32 = 0x811c9dc5;
+        for b in text.bytes() {
+            hash ^= b as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+        COLORS[hash as usize % COLORS.len()]
+    }
 }
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -95,6 +129,11 @@ impl Drawer {
         self.buf
     }
 
+    // bytes written so far, to tell whether something was drawn
+    pub fn buf_len(&self) -> usize {
+        self.buf.len()
+    }
+
     pub fn clear_to_bottom(&mut self) {
         set_background_color(&mut self.buf, Color::Black);
         clear_to_end(&mut self.buf);
@@ -126,21 +165,21 @@ impl Drawer {
         self.buf.extend_from_slice(current_mode_name.as_bytes());
         self.buf.push(b' ');
 
-        let header_help = "[s]status [l]log [b]branches [t]tags [S]stash";
+        let header_help = "[s]status [l]log [b]branches [t]tags [S]stash [H]history [!]shell [Y]copy branch";
         let mut header_help = header_help.as_bytes();
         let current_mode_len = 3 + 1 + current_mode_name.len() + 1;
         let available_width = self.viewport_size.0.saturating_sub(1) as usize;
 
         if current_mode_len + header_help.len() > available_width {
             let overflow_len = current_mode_len + header_help.len() - available_width;
-            header_help = &header_help[..header_help.len() - overflow_len];
+            header_help = &header_help[..header_help.len().saturating_sub(overflow_len)];
         }
 
-        let spacer_len = available_width - current_mode_len - header_help.len();
+        let spacer_len = available_width.saturating_sub(current_mode_len + header_help.len());
 
         set_color(&mut self.buf);
         self.buf.extend(std::iter::repeat(b' ').take(spacer_len));
-        self.buf.extend_from_slice(header_help);
+        help_text(&mut self.buf, header_help, Color::White, foreground_color);
 
         self.next_line();
 
@@ -158,9 +197,9 @@ impl Drawer {
         }
 
         let spacer_len = 1 + available_width - left_help.len() - right_help.len();
-        self.buf.extend_from_slice(left_help);
+        help_text(&mut self.buf, left_help, Color::White, background_color);
         self.buf.extend(std::iter::repeat(b' ').take(spacer_len));
-        self.buf.extend_from_slice(right_help);
+        help_text(&mut self.buf, right_help, Color::White, background_color);
 
         move_cursor_to_next_line(&mut self.buf);
 
@@ -168,6 +207,34 @@ impl Drawer {
         set_foreground_color(&mut self.buf, Color::White);
     }
 
+    // drawn on the last line, which the body leaves free
+    pub fn status_line(&mut self, toggles: &Toggles) {
+        let mut line = String::new();
+        let mut width = 0;
+        let available_width = self.viewport_size.0.saturating_sub(1) as usize;
+        for (name, on) in toggles.entries() {
+            let state = if on { "on" } else { "off" };
+            let len = 1 + name.len() + 2 + state.len();
+            if width + len > available_width {
+                break;
+            }
+            width += len;
+
+            let color = if on { Color::DarkYellow } else { Color::DarkGray };
+            line.push_str(&format!(" {}{}: {}{}", Color::DarkGray, name, color, state));
+        }
+
+        self.buf.extend_from_slice(format!("\x1b[{};1H", self.viewport_size.1).as_bytes());
+        set_background_color(&mut self.buf, Color::Black);
+        clear_until_new_line(&mut self.buf);
+        self.buf.extend_from_slice(line.as_bytes());
+        set_foreground_color(&mut self.buf, Color::White);
+    }
+
+    pub fn background(&mut self, color: Color) {
+        set_background_color(&mut self.buf, color);
+    }
+
     pub fn str(&mut self, line: &str) {
         self.buf.extend_from_slice(line.as_bytes());
     }
@@ -235,8 +302,10 @@ impl Drawer {
         line_count
     }
 
-    pub fn diff(&mut self, output: &Output) -> usize {
+    // 'show_whitespace' marks trailing whitespace and draws tabs as arrows
+    pub fn diff(&mut self, output: &Output, show_whitespace: bool) -> usize {
         let tab_bytes = [b' '; 4];
+        let visible_tab_bytes = "\u{2192}   ".as_bytes();
         let mut utf8_buf = [0; 4];
 
         set_background_color(&mut self.buf, Color::Black);
@@ -251,8 +320,19 @@ impl Drawer {
                 _ => set_foreground_color(&mut self.buf, Color::White),
             }
 
-            for c in line.chars() {
+            // the first column is the diff marker so it's never trailing whitespace
+            let trailing_whitespace_start = if show_whitespace { line.trim_end().len().max(1) } else { line.len() };
+
+            for (i, c) in line.char_indices() {
+                if i == trailing_whitespace_start {
+                    set_background_color(&mut self.buf, Color::DarkRed);
+                }
+
                 match c {
+                    '\t' if show_whitespace => {
+                        self.buf.extend_from_slice(visible_tab_bytes);
+                        x += tab_bytes.len();
+                    }
                     '\t' => {
                         self.buf.extend_from_slice(&tab_bytes);
                         x += tab_bytes.len();
@@ -270,6 +350,73 @@ impl Drawer {
                 }
             }
 
+            set_background_color(&mut self.buf, Color::Black);
+            self.next_line();
+
+            line_count += 1;
+            if line_count + 1 >= self.viewport_size.1 as _ {
+                break;
+            }
+        }
+
+        set_foreground_color(&mut self.buf, Color::White);
+
+        line_count
+    }
+
+    // colors each side of the conflicts in a file, including the base of 'diff3' and 'zdiff3' styles
+    pub fn conflicts(&mut self, output: &Output) -> usize {
+        let tab_bytes = [b' '; 4];
+        let mut utf8_buf = [0; 4];
+
+        let mut color = Color::White;
+        let mut line_count = 0;
+        for (i, line) in output.text().lines().enumerate() {
+            let marker_color = if line.starts_with("<<<<<<<") {
+                Some(Color::DarkGreen)
+            } else if line.starts_with("|||||||") && !matches!(color, Color::White) {
+                Some(Color::DarkYellow)
+            } else if line.starts_with("=======") && !matches!(color, Color::White) {
+                Some(Color::DarkBlue)
+            } else if line.starts_with(">>>>>>>") {
+                Some(Color::White)
+            } else {
+                None
+            };
+            if let Some(marker_color) = marker_color {
+                color = marker_color;
+            }
+
+            if i < output.scroll() {
+                continue;
+            }
+
+            if marker_color.is_some() {
+                set_background_color(&mut self.buf, Color::DarkGray);
+                set_foreground_color(&mut self.buf, Color::White);
+            } else {
+                set_foreground_color(&mut self.buf, color);
+            }
+            let mut x = 0;
+            for c in line.chars() {
+                match c {
+                    '\t' => {
+                        self.buf.extend_from_slice(&tab_bytes);
+                        x += tab_bytes.len();
+                    }
+                    _ => {
+                        let bytes = c.encode_utf8(&mut utf8_buf).as_bytes();
+                        self.buf.extend_from_slice(bytes);
+                        x += 1;
+                    }
+                }
+
+                if x >= self.viewport_size.0 as _ {
+                    x -= self.viewport_size.0 as usize;
+                    line_count += 1;
+                }
+            }
+            set_background_color(&mut self.buf, Color::Black);
             self.next_line();
 
             line_count += 1;
@@ -370,6 +517,33 @@ impl Drawer {
         1
     }
 
+    // 'matches' is (current match, match count)
+    pub fn search(&mut self, readline: &ReadLine, has_focus: bool, matches: (usize, usize)) -> usize {
+        const PREFIX: &str = "search:";
+        set_background_color(&mut self.buf, Color::DarkRed);
+        set_foreground_color(&mut self.buf, Color::White);
+        self.buf.extend_from_slice(PREFIX.as_bytes());
+        self.buf.extend_from_slice(readline.input().as_bytes());
+
+        if has_focus {
+            set_background_color(&mut self.buf, Color::White);
+            self.buf.push(b' ');
+            set_background_color(&mut self.buf, Color::DarkRed);
+        } else if !readline.input().is_empty() {
+            match matches {
+                (_, 0) => self.fmt(format_args!(" no matches")),
+                (current, count) => self.fmt(format_args!(" {}/{}", current + 1, count)),
+            }
+        }
+
+        clear_until_new_line(&mut self.buf);
+        self.next_line();
+        set_background_color(&mut self.buf, Color::Black);
+        set_foreground_color(&mut self.buf, Color::White);
+
+        1
+    }
+
     pub fn select_menu<'entries, I, E>(
         &mut self,
         select: &SelectMenu,
@@ -399,9 +573,8 @@ impl Drawer {
             clear_until_new_line(&mut self.buf);
             move_cursor_to_next_line(&mut self.buf);
 
-            if hovered {
-                set_background_color(&mut self.buf, Color::Black);
-            }
+            // entries may also change the background
+            set_background_color(&mut self.buf, Color::Black);
 
             if line_count >= max_line_count {
                 break;